@@ -0,0 +1,69 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use oicana::simulation::{GameplaySystem, Map, Simulation};
+use std::time::{Duration, Instant};
+
+/// High enough for enemies to survive the whole benchmark
+const ENEMY_HEALTH: i32 = 1_000_000;
+const MAP_LENGTH: usize = 25;
+const TOWERS: usize = 50;
+
+fn populated_simulation(enemies: usize) -> Simulation {
+    let mut simulation = Simulation::with_map(Simulation::synthetic_map(MAP_LENGTH));
+    simulation.spawn_towers(TOWERS);
+    simulation.spawn_enemies(enemies, ENEMY_HEALTH);
+    // get some bullets into the air
+    simulation.step_frames(30);
+    simulation
+}
+
+fn gameplay_systems(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gameplay systems");
+    group.sample_size(20);
+    for system in [
+        GameplaySystem::MoveEnemies,
+        GameplaySystem::Shoot,
+        GameplaySystem::UpdateBullets,
+    ] {
+        for enemies in [100, 1000] {
+            group.bench_function(BenchmarkId::new(format!("{system:?}"), enemies), |b| {
+                let mut simulation = populated_simulation(enemies);
+                let mut schedule = system.schedule();
+                b.iter_custom(|iterations| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iterations {
+                        // keep the world in a steady state without measuring it
+                        simulation.step();
+                        simulation.top_up_enemies(enemies, ENEMY_HEALTH);
+                        let start = Instant::now();
+                        simulation.run_schedule(&mut schedule);
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn full_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("full frame");
+    group.sample_size(20);
+    for enemies in [100, 1000] {
+        group.bench_function(BenchmarkId::from_parameter(enemies), |b| {
+            let mut simulation = populated_simulation(enemies);
+            b.iter(|| {
+                simulation.top_up_enemies(enemies, ENEMY_HEALTH);
+                simulation.step();
+            })
+        });
+    }
+    group.finish();
+}
+
+fn load_map(c: &mut Criterion) {
+    c.bench_function("load map", |b| b.iter(|| black_box(Map::load_map())));
+}
+
+criterion_group!(benches, gameplay_systems, full_frame, load_map);
+criterion_main!(benches);
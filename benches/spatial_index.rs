@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use oicana::spatial::SpatialGrid;
+use rand::prelude::*;
+
+const TILE_SIZE: f32 = 64.;
+const MAP_SIZE: f32 = 800.;
+const TOWER_RANGE: f32 = 160.;
+
+fn positions(count: usize) -> Vec<(Entity, Vec2)> {
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..count)
+        .map(|index| {
+            (
+                Entity::from_raw(index as u32),
+                Vec2::new(rng.gen_range(0.0..MAP_SIZE), rng.gen_range(0.0..MAP_SIZE)),
+            )
+        })
+        .collect()
+}
+
+fn targeting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tower targeting");
+    let towers: Vec<Vec2> = positions(50)
+        .into_iter()
+        .map(|(_, position)| position)
+        .collect();
+    for count in [100, 1000, 5000] {
+        let enemies = positions(count);
+        group.bench_with_input(
+            BenchmarkId::new("linear scan", count),
+            &enemies,
+            |b, enemies| {
+                b.iter(|| {
+                    for tower in towers.iter() {
+                        black_box(
+                            enemies
+                                .iter()
+                                .filter(|(_, position)| position.distance(*tower) < TOWER_RANGE)
+                                .count(),
+                        );
+                    }
+                })
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("grid", count), &enemies, |b, enemies| {
+            let mut grid = SpatialGrid::new(TILE_SIZE);
+            b.iter(|| {
+                grid.clear(TILE_SIZE);
+                for (entity, position) in enemies.iter() {
+                    grid.insert(*entity, *position);
+                }
+                for tower in towers.iter() {
+                    black_box(grid.query_radius(*tower, TOWER_RANGE).count());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, targeting);
+criterion_main!(benches);
@@ -0,0 +1,142 @@
+use crate::enemies::{Enemy, EnemySet, Health, Tameable, ENEMY_STROKE_WIDTH};
+use crate::AppState;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use serde::Deserialize;
+
+const SHIELD_STROKE_WIDTH: f32 = 4.;
+
+pub struct AbilitiesPlugin;
+
+impl Plugin for AbilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                heal_enemies.before(EnemySet::Damage),
+                show_shields,
+                break_shields
+                    .after(EnemySet::ApplyDamage)
+                    .before(EnemySet::UpdateColor),
+            )
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// Special behaviour that can be given to enemies of a wave
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum EnemyAbility {
+    Shielded,
+    Splitting,
+    Healer,
+    Sprinter,
+}
+
+impl EnemyAbility {
+    pub fn insert(&self, entity: &mut EntityCommands, max_health: i32) {
+        match self {
+            EnemyAbility::Shielded => entity.insert(Shield {
+                value: max_health / 2,
+            }),
+            EnemyAbility::Splitting => entity.insert(Splitting { pieces: 2 }),
+            EnemyAbility::Healer => entity.insert(Healer {
+                timer: Timer::from_seconds(2., TimerMode::Repeating),
+                radius: 80.,
+                amount: (max_health / 10).max(1),
+            }),
+            EnemyAbility::Sprinter => entity.insert(Sprinter {
+                cooldown: Timer::from_seconds(3., TimerMode::Repeating),
+                burst: Timer::from_seconds(0.6, TimerMode::Once),
+            }),
+        };
+    }
+}
+
+/// Absorbs all damage until it is used up
+#[derive(Component)]
+pub struct Shield {
+    pub value: i32,
+}
+
+/// Falls apart into smaller enemies when defeated
+#[derive(Component)]
+pub struct Splitting {
+    pub pieces: usize,
+}
+
+/// Regularly restores health of enemies around it
+#[derive(Component)]
+pub struct Healer {
+    timer: Timer,
+    radius: f32,
+    amount: i32,
+}
+
+/// Runs a lot faster for a short time every few seconds
+#[derive(Component)]
+pub struct Sprinter {
+    cooldown: Timer,
+    burst: Timer,
+}
+
+impl Sprinter {
+    pub fn tick(&mut self, delta: std::time::Duration) {
+        self.burst.tick(delta);
+        if self.cooldown.tick(delta).just_finished() {
+            self.burst.reset();
+        }
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        if self.burst.finished() {
+            1.
+        } else {
+            3.
+        }
+    }
+}
+
+fn heal_enemies(
+    time: Res<Time>,
+    mut healer_query: Query<(&Transform, &mut Healer), Without<Tameable>>,
+    mut enemy_query: Query<(&Transform, &Enemy, &mut Health), Without<Tameable>>,
+) {
+    for (healer_transform, mut healer) in healer_query.iter_mut() {
+        if !healer.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        for (transform, enemy, mut health) in enemy_query.iter_mut() {
+            if health.value >= enemy.max_health
+                || transform
+                    .translation
+                    .truncate()
+                    .distance(healer_transform.translation.truncate())
+                    > healer.radius
+            {
+                continue;
+            }
+            health.value = (health.value + healer.amount).min(enemy.max_health);
+        }
+    }
+}
+
+fn show_shields(mut shield_query: Query<&mut Stroke, Added<Shield>>) {
+    for mut stroke in shield_query.iter_mut() {
+        stroke.options.line_width = SHIELD_STROKE_WIDTH;
+    }
+}
+
+fn break_shields(
+    mut commands: Commands,
+    mut shield_query: Query<(Entity, &Shield, &mut Stroke), Changed<Shield>>,
+) {
+    for (entity, shield, mut stroke) in shield_query.iter_mut() {
+        if shield.value > 0 {
+            continue;
+        }
+        stroke.options.line_width = ENEMY_STROKE_WIDTH;
+        commands.entity(entity).remove::<Shield>();
+    }
+}
@@ -0,0 +1,382 @@
+use crate::enemies::Tameable;
+use crate::map::Map;
+use crate::puzzle::{update_pick_source, PickSource};
+use crate::{AppState, CURSOR_Z};
+use bevy::input::gamepad::{GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy::window::CursorMoved;
+use bevy_prototype_lyon::prelude::*;
+use bevy_prototype_lyon::shapes::Circle;
+use serde::Deserialize;
+
+/// Bindings shipped with the game, also used if the config file cannot be read
+const DEFAULT_BINDINGS: &str = include_str!("../config/input.ron");
+#[cfg(not(target_arch = "wasm32"))]
+const BINDINGS_PATH: &str = "config/input.ron";
+/// Virtual cursor speed in units per second
+const CURSOR_SPEED: f32 = 300.;
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .init_resource::<Input<Action>>()
+            .init_resource::<VirtualCursor>()
+            .add_systems(PreUpdate, update_actions.after(InputSystem))
+            .add_systems(OnEnter(AppState::InGame), spawn_virtual_cursor)
+            .add_systems(
+                Update,
+                (
+                    (move_virtual_cursor, cycle_targets, follow_focus)
+                        .chain()
+                        .before(update_pick_source),
+                    show_virtual_cursor,
+                    control_time,
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnExit(AppState::InGame), break_down_actions);
+    }
+}
+
+/// Everything the player can do, independent of the device used to do it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    PickUp,
+    /// Triggers when its binding is let go, like at the end of a drag
+    Place,
+    /// Drop the carried piece without placing it
+    Release,
+    CycleTarget,
+    Reroll,
+    Sell,
+    Pause,
+    SpeedUp,
+    CursorUp,
+    CursorDown,
+    CursorLeft,
+    CursorRight,
+    /// Switch between moving the cursor freely and from tile to tile
+    ToggleGrid,
+    /// Jump to a tower plot, 0 being the tenth
+    Number(u8),
+    /// Select a slot of the puzzle on the plot under the cursor
+    Slot(u8),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    fn pressed(
+        &self,
+        keys: &Input<KeyCode>,
+        mouse_buttons: &Input<MouseButton>,
+        gamepad_buttons: &Input<GamepadButton>,
+        gamepads: &Gamepads,
+    ) -> bool {
+        match self {
+            Binding::Key(key) => keys.pressed(*key),
+            Binding::Mouse(button) => mouse_buttons.pressed(*button),
+            Binding::Gamepad(button_type) => gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, *button_type))),
+        }
+    }
+}
+
+#[derive(Resource, Deserialize)]
+pub struct InputBindings {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        ron::from_str(DEFAULT_BINDINGS).expect("Failed to parse default input bindings")
+    }
+}
+
+impl InputBindings {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load() -> Self {
+        let Ok(config) = std::fs::read_to_string(BINDINGS_PATH) else {
+            return InputBindings::default();
+        };
+        ron::from_str(&config).unwrap_or_else(|error| {
+            warn!("Failed to parse {}: {}", BINDINGS_PATH, error);
+            InputBindings::default()
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn load() -> Self {
+        InputBindings::default()
+    }
+}
+
+/// Cursor moved by arrow keys or a gamepad. Takes over the pick source until the mouse moves again.
+#[derive(Default, Resource)]
+pub struct VirtualCursor {
+    pub position: Vec2,
+    pub active: bool,
+    /// Move from tile to tile instead of freely
+    pub grid: bool,
+    pub focus: Focus,
+}
+
+/// What the virtual cursor is pointing at
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Focus {
+    #[default]
+    Free,
+    Tile,
+    /// Followed while it moves
+    Tameable(Entity),
+    Slot,
+}
+
+impl VirtualCursor {
+    /// Focus to fall back to once the current one is gone
+    pub fn unfocused(&self) -> Focus {
+        if self.grid {
+            Focus::Tile
+        } else {
+            Focus::Free
+        }
+    }
+}
+
+#[derive(Component)]
+struct VirtualCursorSprite;
+
+fn update_actions(
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    mut actions: ResMut<Input<Action>>,
+) {
+    actions.clear();
+    for (action, bindings) in bindings.bindings.iter() {
+        if bindings
+            .iter()
+            .any(|binding| binding.pressed(&keys, &mouse_buttons, &gamepad_buttons, &gamepads))
+        {
+            actions.press(*action);
+        } else {
+            actions.release(*action);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn move_virtual_cursor(
+    time: Res<Time>,
+    map: Res<Map>,
+    actions: Res<Input<Action>>,
+    pick_source: Res<PickSource>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+) {
+    if cursor_moved.iter().count() > 0 {
+        virtual_cursor.active = false;
+    }
+    let mut direction = Vec2::ZERO;
+    for (action, step) in [
+        (Action::CursorUp, Vec2::Y),
+        (Action::CursorDown, Vec2::NEG_Y),
+        (Action::CursorLeft, Vec2::NEG_X),
+        (Action::CursorRight, Vec2::X),
+    ] {
+        if actions.pressed(action) {
+            direction += step;
+        }
+    }
+    for gamepad in gamepads.iter() {
+        let x = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX));
+        let y = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY));
+        direction += Vec2::new(x.unwrap_or(0.), y.unwrap_or(0.));
+    }
+    if direction == Vec2::ZERO || virtual_cursor.grid {
+        return;
+    }
+    if !virtual_cursor.active {
+        virtual_cursor.position = pick_source.last_cursor_pos;
+        virtual_cursor.active = true;
+    }
+    virtual_cursor.focus = Focus::Free;
+    // keep moving while the game is paused
+    let movement = direction.clamp_length_max(1.) * CURSOR_SPEED * time.raw_delta_seconds();
+    let half_tile = Vec2::splat(map.tile_size / 2.);
+    virtual_cursor.position = (virtual_cursor.position + movement).clamp(
+        -half_tile,
+        Vec2::new(map.width as f32, map.height as f32) * map.tile_size - half_tile,
+    );
+}
+
+/// Jump the virtual cursor from one tameable enemy to the next
+fn cycle_targets(
+    actions: Res<Input<Action>>,
+    mut last_target: Local<Option<Entity>>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+    tameable_query: Query<(Entity, &Transform), With<Tameable>>,
+) {
+    if !actions.just_pressed(Action::CycleTarget) {
+        return;
+    }
+    let mut tameables: Vec<(Entity, &Transform)> = tameable_query.iter().collect();
+    tameables.sort_by_key(|(entity, _)| *entity);
+    let next = tameables
+        .iter()
+        .find(|(entity, _)| Some(*entity) > *last_target)
+        .or_else(|| tameables.first());
+    if let Some((entity, transform)) = next {
+        *last_target = Some(*entity);
+        virtual_cursor.position = transform.translation.truncate();
+        virtual_cursor.active = true;
+        virtual_cursor.focus = Focus::Tameable(*entity);
+    }
+}
+
+/// Keep the cursor on the focused tameable while it runs for the clouds
+pub(crate) fn follow_focus(
+    mut virtual_cursor: ResMut<VirtualCursor>,
+    tameable_query: Query<&Transform, With<Tameable>>,
+) {
+    let Focus::Tameable(entity) = virtual_cursor.focus else {
+        return;
+    };
+    match tameable_query.get(entity) {
+        Ok(transform) => virtual_cursor.position = transform.translation.truncate(),
+        Err(_) => virtual_cursor.focus = virtual_cursor.unfocused(),
+    }
+}
+
+fn spawn_virtual_cursor(mut commands: Commands) {
+    commands.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&Circle {
+                radius: 8.,
+                center: Vec2::ZERO,
+            }),
+            transform: Transform::from_xyz(0., 0., CURSOR_Z),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Stroke::new(Color::WHITE, 2.),
+        VirtualCursorSprite,
+    ));
+}
+
+/// Outline what the cursor focuses
+fn show_virtual_cursor(
+    map: Res<Map>,
+    virtual_cursor: Res<VirtualCursor>,
+    mut shown_focus: Local<Option<Focus>>,
+    mut cursor_query: Query<
+        (&mut Transform, &mut Visibility, &mut Path),
+        With<VirtualCursorSprite>,
+    >,
+) {
+    for (mut transform, mut visibility, mut path) in cursor_query.iter_mut() {
+        transform.translation = virtual_cursor.position.extend(CURSOR_Z);
+        *visibility = if virtual_cursor.active {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+        if *shown_focus == Some(virtual_cursor.focus) {
+            continue;
+        }
+        *path = match virtual_cursor.focus {
+            Focus::Free => GeometryBuilder::build_as(&Circle {
+                radius: 8.,
+                center: Vec2::ZERO,
+            }),
+            Focus::Tameable(_) => GeometryBuilder::build_as(&Circle {
+                radius: 14.,
+                center: Vec2::ZERO,
+            }),
+            Focus::Tile => GeometryBuilder::build_as(&shapes::Rectangle {
+                extents: Vec2::splat(map.tile_size - 4.),
+                origin: RectangleOrigin::Center,
+            }),
+            Focus::Slot => GeometryBuilder::build_as(&shapes::Rectangle {
+                extents: Vec2::splat(28.),
+                origin: RectangleOrigin::Center,
+            }),
+        };
+    }
+    *shown_focus = Some(virtual_cursor.focus);
+}
+
+fn control_time(actions: Res<Input<Action>>, mut time: ResMut<Time>) {
+    if actions.just_pressed(Action::Pause) {
+        if time.is_paused() {
+            time.unpause();
+        } else {
+            time.pause();
+        }
+    }
+    if actions.just_pressed(Action::SpeedUp) {
+        let speed = if time.relative_speed() > 1. { 1. } else { 2. };
+        time.set_relative_speed(speed);
+    }
+}
+
+fn break_down_actions(
+    mut commands: Commands,
+    mut time: ResMut<Time>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+    cursor_query: Query<Entity, With<VirtualCursorSprite>>,
+) {
+    time.unpause();
+    time.set_relative_speed(1.);
+    virtual_cursor.active = false;
+    virtual_cursor.grid = false;
+    virtual_cursor.focus = Focus::Free;
+    for entity in cursor_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_cover_all_actions() {
+        let bindings = InputBindings::default();
+        for action in [
+            Action::PickUp,
+            Action::Place,
+            Action::Release,
+            Action::CycleTarget,
+            Action::Reroll,
+            Action::Sell,
+            Action::Pause,
+            Action::SpeedUp,
+            Action::CursorUp,
+            Action::CursorDown,
+            Action::CursorLeft,
+            Action::CursorRight,
+            Action::ToggleGrid,
+        ]
+        .into_iter()
+        .chain((0..=9).map(Action::Number))
+        .chain((1..=4).map(Action::Slot))
+        {
+            assert!(!bindings.bindings[&action].is_empty(), "{action:?}");
+        }
+    }
+}
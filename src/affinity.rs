@@ -0,0 +1,81 @@
+use crate::enemies::EnemyColor;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+/// Matrix shipped with the game, also used if the config file cannot be read
+const DEFAULT_AFFINITIES: &str = include_str!("../config/affinity.ron");
+#[cfg(not(target_arch = "wasm32"))]
+const AFFINITIES_PATH: &str = "config/affinity.ron";
+
+/// How effective towers of one color are against enemies of another color.
+/// Pairs without an entry deal normal damage.
+#[derive(Resource, Deserialize)]
+pub struct AffinityMatrix {
+    multipliers: HashMap<(EnemyColor, EnemyColor), f32>,
+}
+
+impl AffinityMatrix {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        let Ok(config) = std::fs::read_to_string(AFFINITIES_PATH) else {
+            return AffinityMatrix::default();
+        };
+        ron::from_str(&config).unwrap_or_else(|error| {
+            warn!("Failed to parse {}: {}", AFFINITIES_PATH, error);
+            AffinityMatrix::default()
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> Self {
+        AffinityMatrix::default()
+    }
+
+    pub fn multiplier(&self, attacker: Option<&EnemyColor>, defender: &EnemyColor) -> f32 {
+        attacker
+            .and_then(|attacker| {
+                self.multipliers
+                    .get(&(attacker.clone(), defender.clone()))
+                    .copied()
+            })
+            .unwrap_or(1.)
+    }
+
+    pub fn apply(&self, damage: i32, attacker: Option<&EnemyColor>, defender: &EnemyColor) -> i32 {
+        (damage as f32 * self.multiplier(attacker, defender)).round() as i32
+    }
+}
+
+impl Default for AffinityMatrix {
+    fn default() -> Self {
+        ron::from_str(DEFAULT_AFFINITIES).expect("Failed to parse default color affinities")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_are_strong_weak_or_neutral() {
+        let matrix = AffinityMatrix::default();
+        assert_eq!(
+            matrix.multiplier(Some(&EnemyColor::Red), &EnemyColor::Green),
+            1.5
+        );
+        assert_eq!(
+            matrix.multiplier(Some(&EnemyColor::Red), &EnemyColor::Blue),
+            0.5
+        );
+        assert_eq!(
+            matrix.multiplier(Some(&EnemyColor::Red), &EnemyColor::Pink),
+            1.
+        );
+        assert_eq!(matrix.multiplier(None, &EnemyColor::Green), 1.);
+        assert_eq!(
+            matrix.apply(15, Some(&EnemyColor::Blue), &EnemyColor::Red),
+            23
+        );
+    }
+}
@@ -1,60 +1,246 @@
-use crate::enemies::EnemyBreach;
+use crate::enemies::{EnemyBreach, EnemyDefeated, WaveStarted};
 use crate::loading::AudioAssets;
-use crate::towers::TowerShot;
+use crate::puzzle::{CompletePuzzle, PiecePickedUp, PiecePlaced, WrongSlot};
+use crate::settings::Settings;
+use crate::towers::{TowerBuilt, TowerShot};
 use crate::AppState;
 use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioApp, AudioChannel, AudioControl, AudioPlugin};
+use bevy::utils::HashMap;
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioControl, AudioPlugin, AudioSource, AudioTween};
+use std::time::Duration;
+
+/// Seconds before the same tower can be heard again
+const SHOT_SOUND_INTERVAL: f64 = 0.25;
+/// Most shots to be heard in one frame
+const MAX_SHOT_SOUNDS: usize = 3;
+/// How far sounds at the edge of the screen are panned. 1 would be fully left or right.
+const PAN_STRENGTH: f32 = 0.8;
+const SFX_FADE_OUT: Duration = Duration::from_millis(500);
 
 pub struct InternalAudioPlugin;
 
 impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(AudioPlugin)
-            .add_audio_channel::<BackgroundAudio>()
-            .add_systems(OnEnter(AppState::Menu), start_audio)
+            .add_audio_channel::<MusicChannel>()
+            .add_audio_channel::<CombatChannel>()
+            .add_audio_channel::<UiChannel>()
+            .add_systems(Update, apply_volume)
             .add_systems(
                 Update,
-                (tower_shots, enemy_breach).run_if(in_state(AppState::InGame)),
+                (
+                    tower_shots,
+                    enemy_breach,
+                    enemy_defeated,
+                    wave_started,
+                    tower_built,
+                    piece_picked_up,
+                    piece_placed,
+                    wrong_slot,
+                    puzzle_completed,
+                )
+                    .run_if(in_state(AppState::InGame)),
             )
             .add_systems(OnExit(AppState::InGame), stop_audio);
     }
 }
 
+/// Background music, see [`crate::music`]
 #[derive(Resource)]
-struct BackgroundAudio;
+pub struct MusicChannel;
 
-fn start_audio(
-    audio_assets: Res<AudioAssets>,
-    background_channel: Res<AudioChannel<BackgroundAudio>>,
-    audio: Res<Audio>,
+/// Shots, breaches and everything else happening on the map
+#[derive(Resource)]
+pub struct CombatChannel;
+
+/// Feedback for the player's own actions
+#[derive(Resource)]
+pub struct UiChannel;
+
+fn apply_volume(
+    settings: Res<Settings>,
+    music_channel: Res<AudioChannel<MusicChannel>>,
+    combat_channel: Res<AudioChannel<CombatChannel>>,
+    ui_channel: Res<AudioChannel<UiChannel>>,
+) {
+    if settings.is_changed() {
+        music_channel.set_volume(settings.music_volume());
+        combat_channel.set_volume(settings.sfx_volume());
+        ui_channel.set_volume(settings.ui_volume());
+    }
+}
+
+fn stop_audio(
+    combat_channel: Res<AudioChannel<CombatChannel>>,
+    ui_channel: Res<AudioChannel<UiChannel>>,
+) {
+    combat_channel
+        .stop()
+        .fade_out(AudioTween::linear(SFX_FADE_OUT));
+    ui_channel.stop().fade_out(AudioTween::linear(SFX_FADE_OUT));
+}
+
+/// Play the sound once, no matter how many events were sent this frame
+fn play_on_event<E: Event, C: Resource>(
+    reader: &mut EventReader<E>,
+    channel: &AudioChannel<C>,
+    sound: &Handle<AudioSource>,
 ) {
-    audio.set_volume(0.15);
-    background_channel.set_volume(0.15);
-    background_channel
-        .play(audio_assets.background.clone())
-        .looped();
+    if reader.iter().last().is_some() {
+        channel.play(sound.clone());
+    }
 }
 
-fn stop_audio(audio: Res<Audio>) {
-    audio.stop();
+/// Stereo panning of a sound at `x`, from 0 (left) over 0.5 (center) to 1 (right)
+fn panning(x: f32, camera: Option<(&GlobalTransform, &OrthographicProjection)>) -> f64 {
+    let Some((transform, projection)) = camera else {
+        return 0.5;
+    };
+    let half_width = projection.area.width() / 2.;
+    if half_width <= 0. {
+        return 0.5;
+    }
+    let offset = ((x - transform.translation().x) / half_width).clamp(-1., 1.);
+    (0.5 + offset * PAN_STRENGTH / 2.) as f64
 }
 
 fn tower_shots(
+    time: Res<Time>,
     audio_assets: Res<AudioAssets>,
     mut tower_shot_reader: EventReader<TowerShot>,
-    audio: Res<Audio>,
+    mut last_heard: Local<HashMap<Entity, f64>>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+    combat_channel: Res<AudioChannel<CombatChannel>>,
 ) {
-    if tower_shot_reader.iter().last().is_some() {
-        audio.play(audio_assets.tower_shots.clone());
+    let now = time.elapsed_seconds_f64();
+    last_heard.retain(|_, heard| now - *heard < SHOT_SOUND_INTERVAL);
+    let camera = camera_query.get_single().ok();
+    let mut played = 0;
+    for shot in tower_shot_reader.iter() {
+        if played >= MAX_SHOT_SOUNDS || last_heard.contains_key(&shot.tower) {
+            continue;
+        }
+        last_heard.insert(shot.tower, now);
+        played += 1;
+        combat_channel
+            .play(audio_assets.tower_shots.clone())
+            .with_panning(panning(shot.position.x, camera));
     }
 }
 
 fn enemy_breach(
     audio_assets: Res<AudioAssets>,
     mut enemy_breach_reader: EventReader<EnemyBreach>,
-    audio: Res<Audio>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<Camera2d>>,
+    combat_channel: Res<AudioChannel<CombatChannel>>,
+) {
+    if let Some(breach) = enemy_breach_reader.iter().last() {
+        combat_channel
+            .play(audio_assets.enemy_breach.clone())
+            .with_panning(panning(breach.position.x, camera_query.get_single().ok()));
+    }
+}
+
+fn enemy_defeated(
+    audio_assets: Res<AudioAssets>,
+    mut enemy_defeated_reader: EventReader<EnemyDefeated>,
+    combat_channel: Res<AudioChannel<CombatChannel>>,
+) {
+    play_on_event(
+        &mut enemy_defeated_reader,
+        &combat_channel,
+        &audio_assets.enemy_defeated,
+    );
+}
+
+fn wave_started(
+    audio_assets: Res<AudioAssets>,
+    mut wave_started_reader: EventReader<WaveStarted>,
+    combat_channel: Res<AudioChannel<CombatChannel>>,
 ) {
-    if enemy_breach_reader.iter().last().is_some() {
-        audio.play(audio_assets.enemy_breach.clone());
+    play_on_event(
+        &mut wave_started_reader,
+        &combat_channel,
+        &audio_assets.wave_start,
+    );
+}
+
+fn tower_built(
+    audio_assets: Res<AudioAssets>,
+    mut tower_built_reader: EventReader<TowerBuilt>,
+    combat_channel: Res<AudioChannel<CombatChannel>>,
+) {
+    let Some(tower_built) = tower_built_reader.iter().last() else {
+        return;
+    };
+    // upgrades sound a bit brighter than new towers
+    let playback_rate = if tower_built.upgrade { 1.25 } else { 1. };
+    combat_channel
+        .play(audio_assets.tower_built.clone())
+        .with_playback_rate(playback_rate);
+}
+
+fn piece_picked_up(
+    audio_assets: Res<AudioAssets>,
+    mut picked_up_reader: EventReader<PiecePickedUp>,
+    ui_channel: Res<AudioChannel<UiChannel>>,
+) {
+    play_on_event(
+        &mut picked_up_reader,
+        &ui_channel,
+        &audio_assets.piece_picked_up,
+    );
+}
+
+fn piece_placed(
+    audio_assets: Res<AudioAssets>,
+    mut placed_reader: EventReader<PiecePlaced>,
+    ui_channel: Res<AudioChannel<UiChannel>>,
+) {
+    play_on_event(&mut placed_reader, &ui_channel, &audio_assets.piece_placed);
+}
+
+fn wrong_slot(
+    audio_assets: Res<AudioAssets>,
+    mut wrong_slot_reader: EventReader<WrongSlot>,
+    ui_channel: Res<AudioChannel<UiChannel>>,
+) {
+    play_on_event(
+        &mut wrong_slot_reader,
+        &ui_channel,
+        &audio_assets.wrong_slot,
+    );
+}
+
+fn puzzle_completed(
+    audio_assets: Res<AudioAssets>,
+    mut complete_puzzle_reader: EventReader<CompletePuzzle>,
+    ui_channel: Res<AudioChannel<UiChannel>>,
+) {
+    play_on_event(
+        &mut complete_puzzle_reader,
+        &ui_channel,
+        &audio_assets.puzzle_completed,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::Rect;
+
+    #[test]
+    fn sounds_are_panned_towards_their_side_of_the_screen() {
+        let transform = GlobalTransform::from_translation(Vec3::new(100., 0., 0.));
+        let projection = OrthographicProjection {
+            area: Rect::new(-200., -150., 200., 150.),
+            ..Default::default()
+        };
+        let camera = Some((&transform, &projection));
+        assert_eq!(panning(100., camera), 0.5);
+        assert!(panning(0., camera) < 0.5);
+        assert!(panning(250., camera) > 0.5);
+        assert!((panning(10_000., camera) - 0.9).abs() < 0.001);
+        assert_eq!(panning(0., None), 0.5);
     }
 }
@@ -0,0 +1,139 @@
+use crate::enemies::{create_enemy, Enemy, EnemyColor, EnemySet, Health, Tameable};
+use crate::status_effects::StatusEffects;
+use crate::AppState;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_boss_phases
+                .after(EnemySet::ApplyDamage)
+                .before(EnemySet::UpdateColor)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// Boss at the end of a wave. The boss is much stronger than the enemies of its wave.
+#[derive(Clone, Deserialize)]
+pub struct BossDefinition {
+    pub health_multiplier: i32,
+    pub phases: Vec<BossPhase>,
+}
+
+/// Behaviour a boss switches to once its health drops below the threshold
+#[derive(Clone, Deserialize)]
+pub struct BossPhase {
+    /// Fraction of the boss' maximum health
+    pub health_threshold: f32,
+    pub behaviour: PhaseBehaviour,
+}
+
+#[derive(Clone, Deserialize)]
+pub enum PhaseBehaviour {
+    SpawnMinions(usize),
+    SpeedUp(f32),
+    Immune(EnemyColor),
+}
+
+#[derive(Component)]
+pub struct Boss {
+    phases: Vec<BossPhase>,
+    next_phase: usize,
+    pub speed_multiplier: f32,
+    pub immune_to: Option<EnemyColor>,
+}
+
+impl Boss {
+    /// Hits of this color neither hurt the boss nor apply their status effects
+    pub fn is_immune_to(&self, color: Option<&EnemyColor>) -> bool {
+        self.immune_to.is_some() && self.immune_to.as_ref() == color
+    }
+}
+
+/// Defeated bosses can fill all open slots of a puzzle at once
+#[derive(Component)]
+pub struct Wildcard;
+
+pub fn spawn_boss(
+    commands: &mut Commands,
+    definition: &BossDefinition,
+    enemy: Enemy,
+    transform: Transform,
+) -> Entity {
+    let health = enemy.max_health * definition.health_multiplier;
+    let enemy = Enemy::new(enemy.form, enemy.color, health);
+    commands
+        .spawn(
+            enemy
+                .form
+                .build_boss_bundle(transform, Color::GRAY, Some(Color::GRAY)),
+        )
+        .insert(enemy)
+        .insert(Health { value: health })
+        .insert(StatusEffects::default())
+        .insert(Boss {
+            phases: definition.phases.clone(),
+            next_phase: 0,
+            speed_multiplier: 1.,
+            immune_to: None,
+        })
+        .id()
+}
+
+fn update_boss_phases(
+    mut commands: Commands,
+    mut boss_query: Query<(&mut Boss, &Enemy, &Health, &Transform), Without<Tameable>>,
+) {
+    for (mut boss, enemy, health, transform) in boss_query.iter_mut() {
+        while let Some(phase) = boss.phases.get(boss.next_phase).cloned() {
+            if health.value as f32 > enemy.max_health as f32 * phase.health_threshold {
+                break;
+            }
+            boss.next_phase += 1;
+            match phase.behaviour {
+                PhaseBehaviour::SpawnMinions(count) => {
+                    for minion in 0..count {
+                        let mut minion_enemy = Enemy::new(
+                            enemy.form.clone(),
+                            enemy.color.clone(),
+                            (enemy.max_health / 10).max(1),
+                        );
+                        minion_enemy.follow(enemy);
+                        minion_enemy.travelled -= minion as f32 * 10.;
+                        let mut transform = *transform;
+                        transform.translation.y += minion as f32 * 8.;
+                        transform.scale = Vec3::ONE;
+                        create_enemy(&mut commands, minion_enemy, transform);
+                    }
+                }
+                PhaseBehaviour::SpeedUp(multiplier) => boss.speed_multiplier *= multiplier,
+                PhaseBehaviour::Immune(color) => boss.immune_to = Some(color),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn immunity_covers_only_its_color() {
+        let mut boss = Boss {
+            phases: vec![],
+            next_phase: 0,
+            speed_multiplier: 1.,
+            immune_to: None,
+        };
+        assert!(!boss.is_immune_to(None));
+        boss.immune_to = Some(EnemyColor::Red);
+        assert!(boss.is_immune_to(Some(&EnemyColor::Red)));
+        assert!(!boss.is_immune_to(Some(&EnemyColor::Blue)));
+        assert!(!boss.is_immune_to(None));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::boss::Wildcard;
+use crate::enemies::{Enemy, Tameable};
+use crate::puzzle::{
+    puzzle_input, update_pick_source, CurrentPiece, PickSource, Piece, PuzzleSlot,
+};
+use crate::AppState;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// Seconds between two actions of a bot
+const THINK_TIME: f32 = 0.3;
+
+/// Plays the puzzle part of the game while a [`Bot`] resource exists
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            play.after(update_pick_source)
+                .before(puzzle_input)
+                .run_if(in_state(AppState::InGame))
+                .run_if(resource_exists::<Bot>()),
+        )
+        .add_systems(OnExit(AppState::InGame), remove_bot);
+    }
+}
+
+#[derive(Resource)]
+pub struct Bot {
+    strategy: Box<dyn Strategy>,
+    timer: Timer,
+}
+
+impl Bot {
+    pub fn new(strategy: impl Strategy + 'static) -> Self {
+        Bot {
+            strategy: Box::new(strategy),
+            timer: Timer::from_seconds(THINK_TIME, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Decides what to do next based on what is currently going on in the game
+pub trait Strategy: Send + Sync {
+    fn decide(&mut self, observation: &Observation) -> Option<BotAction>;
+}
+
+/// The same actions a player performs with the mouse
+#[derive(Clone, Debug, PartialEq)]
+pub enum BotAction {
+    PickUp(Vec2),
+    Place(Vec2),
+    /// Let the carried piece go
+    Release,
+}
+
+#[derive(Clone, Debug)]
+pub struct TameableView {
+    pub position: Vec2,
+    pub piece: Piece,
+    pub wildcard: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SlotView {
+    pub position: Vec2,
+    pub piece: Piece,
+    pub puzzle_id: usize,
+    pub filled: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Observation {
+    /// Tameable enemies that are not carried
+    pub tameables: Vec<TameableView>,
+    pub slots: Vec<SlotView>,
+    pub carrying: Option<Piece>,
+    pub carrying_wildcard: bool,
+}
+
+impl Observation {
+    /// Open slots the piece can be placed in
+    pub fn fitting_slots<'a>(
+        &'a self,
+        piece: &'a Piece,
+        wildcard: bool,
+    ) -> impl Iterator<Item = &'a SlotView> + 'a {
+        self.slots
+            .iter()
+            .filter(move |slot| !slot.filled && (wildcard || &slot.piece == piece))
+    }
+
+    pub fn filled_slots(&self, puzzle_id: usize) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.puzzle_id == puzzle_id && slot.filled)
+            .count()
+    }
+}
+
+/// Works on the puzzles closest to completion. Wildcards go to the emptiest puzzle.
+pub struct GreedyStrategy;
+
+impl GreedyStrategy {
+    fn best_slot<'a>(
+        observation: &'a Observation,
+        piece: &'a Piece,
+        wildcard: bool,
+    ) -> Option<&'a SlotView> {
+        let slots = observation.fitting_slots(piece, wildcard);
+        if wildcard {
+            slots.min_by_key(|slot| observation.filled_slots(slot.puzzle_id))
+        } else {
+            slots.max_by_key(|slot| observation.filled_slots(slot.puzzle_id))
+        }
+    }
+}
+
+impl Strategy for GreedyStrategy {
+    fn decide(&mut self, observation: &Observation) -> Option<BotAction> {
+        if let Some(piece) = observation.carrying.as_ref() {
+            return Some(
+                match GreedyStrategy::best_slot(observation, piece, observation.carrying_wildcard) {
+                    Some(slot) => BotAction::Place(slot.position),
+                    None => BotAction::Release,
+                },
+            );
+        }
+        observation
+            .tameables
+            .iter()
+            .filter_map(|tameable| {
+                let slot =
+                    GreedyStrategy::best_slot(observation, &tameable.piece, tameable.wildcard)?;
+                let score = if tameable.wildcard {
+                    4
+                } else {
+                    observation.filled_slots(slot.puzzle_id)
+                };
+                Some((tameable, score))
+            })
+            .max_by_key(|(_, score)| *score)
+            .map(|(tameable, _)| BotAction::PickUp(tameable.position))
+    }
+}
+
+/// Picks up any piece and puts it into any slot it fits
+pub struct RandomStrategy {
+    rng: StdRng,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: u64) -> Self {
+        RandomStrategy {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn decide(&mut self, observation: &Observation) -> Option<BotAction> {
+        if let Some(piece) = observation.carrying.as_ref() {
+            let slots: Vec<&SlotView> = observation
+                .fitting_slots(piece, observation.carrying_wildcard)
+                .collect();
+            return Some(match slots.choose(&mut self.rng) {
+                Some(slot) => BotAction::Place(slot.position),
+                None => BotAction::Release,
+            });
+        }
+        observation
+            .tameables
+            .choose(&mut self.rng)
+            .map(|tameable| BotAction::PickUp(tameable.position))
+    }
+}
+
+fn play(
+    time: Res<Time>,
+    mut bot: ResMut<Bot>,
+    mut pick_source: ResMut<PickSource>,
+    current_piece: Res<CurrentPiece>,
+    tameable_query: Query<(Entity, &Transform, &Enemy, Option<&Wildcard>), With<Tameable>>,
+    slot_query: Query<(&Transform, &PuzzleSlot)>,
+) {
+    // the bot is in control of the pick source
+    pick_source.just_pressed = false;
+    pick_source.just_released = false;
+    pick_source.just_canceled = false;
+    if !bot.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let observation = Observation {
+        tameables: tameable_query
+            .iter()
+            .filter(|(entity, ..)| current_piece.entity != Some(*entity))
+            .map(|(_, transform, enemy, wildcard)| TameableView {
+                position: transform.translation.truncate(),
+                piece: Piece {
+                    color: enemy.color.clone(),
+                    form: enemy.form.clone(),
+                },
+                wildcard: wildcard.is_some(),
+            })
+            .collect(),
+        slots: slot_query
+            .iter()
+            .map(|(transform, slot)| SlotView {
+                position: transform.translation.truncate(),
+                piece: slot.piece.clone(),
+                puzzle_id: slot.puzzle_id,
+                filled: slot.filled,
+            })
+            .collect(),
+        carrying: current_piece.entity.and(current_piece.piece.clone()),
+        carrying_wildcard: current_piece.wildcard,
+    };
+    match bot.strategy.decide(&observation) {
+        Some(BotAction::PickUp(position)) => {
+            pick_source.last_cursor_pos = position;
+            pick_source.just_pressed = true;
+        }
+        Some(BotAction::Place(position)) => {
+            pick_source.last_cursor_pos = position;
+            pick_source.just_released = true;
+        }
+        Some(BotAction::Release) => pick_source.just_canceled = true,
+        None => {}
+    }
+}
+
+/// The next game is played by the player again, unless a bot is added for it
+fn remove_bot(mut commands: Commands) {
+    commands.remove_resource::<Bot>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enemies::{EnemyColor, EnemyForm};
+
+    fn piece(color: EnemyColor, form: EnemyForm) -> Piece {
+        Piece { color, form }
+    }
+
+    fn slot(x: f32, piece: Piece, puzzle_id: usize, filled: bool) -> SlotView {
+        SlotView {
+            position: Vec2::new(x, 0.),
+            piece,
+            puzzle_id,
+            filled,
+        }
+    }
+
+    fn tameable(x: f32, piece: Piece) -> TameableView {
+        TameableView {
+            position: Vec2::new(x, 0.),
+            piece,
+            wildcard: false,
+        }
+    }
+
+    #[test]
+    fn greedy_picks_piece_for_the_fullest_puzzle() {
+        let red_circle = piece(EnemyColor::Red, EnemyForm::Circle);
+        let blue_circle = piece(EnemyColor::Blue, EnemyForm::Circle);
+        let observation = Observation {
+            tameables: vec![
+                tameable(1., red_circle.clone()),
+                tameable(2., blue_circle.clone()),
+            ],
+            slots: vec![
+                slot(10., red_circle.clone(), 0, false),
+                slot(20., blue_circle.clone(), 1, false),
+                slot(21., red_circle.clone(), 1, true),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            GreedyStrategy.decide(&observation),
+            Some(BotAction::PickUp(Vec2::new(2., 0.)))
+        );
+    }
+
+    #[test]
+    fn greedy_releases_piece_that_fits_nowhere() {
+        let observation = Observation {
+            slots: vec![slot(
+                10.,
+                piece(EnemyColor::Red, EnemyForm::Circle),
+                0,
+                false,
+            )],
+            carrying: Some(piece(EnemyColor::Green, EnemyForm::Triangle)),
+            ..Default::default()
+        };
+        assert_eq!(
+            GreedyStrategy.decide(&observation),
+            Some(BotAction::Release)
+        );
+    }
+
+    #[test]
+    fn random_places_carried_piece_into_fitting_slot() {
+        let red_circle = piece(EnemyColor::Red, EnemyForm::Circle);
+        let observation = Observation {
+            slots: vec![
+                slot(10., piece(EnemyColor::Blue, EnemyForm::Circle), 0, false),
+                slot(20., red_circle.clone(), 0, true),
+                slot(30., red_circle.clone(), 1, false),
+            ],
+            carrying: Some(red_circle),
+            ..Default::default()
+        };
+        assert_eq!(
+            RandomStrategy::new(7).decide(&observation),
+            Some(BotAction::Place(Vec2::new(30., 0.)))
+        );
+    }
+}
@@ -1,60 +1,191 @@
-use crate::enemies::{Enemy, EnemySet, Health, Tameable};
+use crate::affinity::AffinityMatrix;
+use crate::boss::Boss;
+use crate::enemies::{DamageEnemy, Enemy, EnemyColor, EnemySet, Tameable};
+use crate::spatial::EnemyGrid;
+use crate::status_effects::{StatusEffect, StatusEffects};
 use crate::AppState;
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use bevy_prototype_lyon::shapes::Circle;
 
+/// Distance between bullet and enemy center that counts as a hit
+const HIT_RADIUS: f32 = 12.;
+
 pub struct BulletPlugin;
 
 impl Plugin for BulletPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            update_bullets
-                .in_set(EnemySet::Damage)
-                .run_if(in_state(AppState::InGame)),
-        )
-        .add_systems(OnExit(AppState::InGame), break_down_bullets);
+        app.insert_resource(AffinityMatrix::load())
+            .add_systems(
+                Update,
+                update_bullets
+                    .in_set(EnemySet::Damage)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnExit(AppState::InGame), break_down_bullets);
     }
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProjectileKind {
+    /// Follows its target and keeps flying straight if the target is gone
+    Homing,
+    /// Flies straight to where the target will be
+    Predictive,
+    /// Explodes where the target will be and damages all enemies around
+    Burst { radius: f32 },
+}
+
 #[derive(Component)]
 pub struct Bullet {
     pub damage: i32,
     pub speed: f32,
+    pub color: Option<EnemyColor>,
+    /// Applied to the enemy on hit
+    pub effects: Vec<StatusEffect>,
+    pub kind: ProjectileKind,
+    target: Option<Entity>,
+    velocity: Vec2,
+    destination: Vec2,
+    lifetime: Timer,
 }
 
-fn update_bullets(
+impl Bullet {
+    pub fn new(kind: ProjectileKind, damage: i32, speed: f32) -> Self {
+        Bullet {
+            damage,
+            speed,
+            color: None,
+            effects: vec![],
+            kind,
+            target: None,
+            velocity: Vec2::ZERO,
+            destination: Vec2::ZERO,
+            lifetime: Timer::from_seconds(3., TimerMode::Once),
+        }
+    }
+
+    pub fn aim(
+        &mut self,
+        origin: Vec2,
+        target: Entity,
+        target_position: Vec2,
+        target_velocity: Vec2,
+    ) {
+        self.target = Some(target);
+        let direction = match self.kind {
+            ProjectileKind::Homing => target_position - origin,
+            ProjectileKind::Predictive | ProjectileKind::Burst { .. } => {
+                let time = intercept_time(target_position - origin, target_velocity, self.speed);
+                self.destination = target_position + target_velocity * time;
+                self.destination - origin
+            }
+        };
+        self.velocity = direction.normalize_or_zero() * self.speed;
+    }
+}
+
+/// Time after which a bullet fired now meets a target moving with constant velocity.
+/// Falls back to the time needed to reach the current target position if the bullet is too slow.
+fn intercept_time(offset: Vec2, target_velocity: Vec2, speed: f32) -> f32 {
+    let a = target_velocity.length_squared() - speed * speed;
+    let b = 2. * offset.dot(target_velocity);
+    let c = offset.length_squared();
+    let direct = offset.length() / speed;
+    if a.abs() < f32::EPSILON {
+        return if b < 0. { -c / b } else { direct };
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return direct;
+    }
+    let root = discriminant.sqrt();
+    [(-b - root) / (2. * a), (-b + root) / (2. * a)]
+        .into_iter()
+        .filter(|time| *time > 0.)
+        .min_by(|a, b| a.total_cmp(b))
+        .unwrap_or(direct)
+}
+
+/// An enemy a bullet can hit and what decides the effects of the hit
+type HitEnemy = (
+    Entity,
+    &'static Transform,
+    &'static Enemy,
+    Option<&'static mut StatusEffects>,
+    Option<&'static Boss>,
+);
+
+pub(crate) fn update_bullets(
     mut commands: Commands,
-    mut bullet_query: Query<(Entity, &Bullet, &mut Transform), Without<Enemy>>,
-    mut enemy_query: Query<(&Transform, &mut Health, &mut Enemy), Without<Tameable>>,
+    mut bullet_query: Query<(Entity, &mut Bullet, &mut Transform), Without<Enemy>>,
+    mut enemy_query: Query<HitEnemy, Without<Tameable>>,
+    enemy_grid: Res<EnemyGrid>,
+    mut damage_enemy: EventWriter<DamageEnemy>,
+    affinity_matrix: Res<AffinityMatrix>,
     time: Res<Time>,
 ) {
-    let delta = time.delta().as_secs_f32();
-    for (target_transform, mut health, mut enemy) in enemy_query.iter_mut() {
-        let mut to_remove: Vec<Entity> = vec![];
-        for bullet_id in enemy.bullets.iter() {
-            let bullet = bullet_query.get_mut(*bullet_id);
-            if let Ok((bullet_entity, bullet, mut transform)) = bullet {
-                let mut distance = target_transform.translation - transform.translation;
-                distance.z = 0.;
-                if distance.length() < bullet.speed * delta {
-                    health.value -= bullet.damage;
-                    commands.entity(bullet_entity).despawn();
-                    to_remove.push(*bullet_id);
-                } else {
-                    let movement = distance.normalize() * bullet.speed * delta;
-                    transform.translation += movement;
-                }
+    let delta = time.delta_seconds();
+    for (bullet_entity, mut bullet, mut transform) in bullet_query.iter_mut() {
+        if bullet.lifetime.tick(time.delta()).finished() {
+            commands.entity(bullet_entity).despawn();
+            continue;
+        }
+        if bullet.kind == ProjectileKind::Homing {
+            let target_position = bullet
+                .target
+                .and_then(|target| enemy_query.get(target).ok())
+                .map(|(_, target_transform, _, _, _)| target_transform.translation.truncate());
+            if let Some(target_position) = target_position {
+                let direction = target_position - transform.translation.truncate();
+                bullet.velocity = direction.normalize_or_zero() * bullet.speed;
             } else {
-                to_remove.push(*bullet_id);
+                bullet.target = None;
             }
         }
-        enemy.bullets = enemy
-            .bullets
-            .drain(..)
-            .filter(|entity| !to_remove.contains(entity))
-            .collect();
+        transform.translation += (bullet.velocity * delta).extend(0.);
+        let position = transform.translation.truncate();
+
+        let hit_enemies: Vec<Entity> = match bullet.kind {
+            ProjectileKind::Burst { radius } => {
+                // still on the way, bursts only hit once they passed their destination
+                if (bullet.destination - position).dot(bullet.velocity) > 0. {
+                    continue;
+                }
+                enemy_grid
+                    .query_radius(position, radius)
+                    .map(|(entity, _)| entity)
+                    .collect()
+            }
+            _ => enemy_grid
+                .closest(position, HIT_RADIUS)
+                .map(|(entity, _)| entity)
+                .into_iter()
+                .collect(),
+        };
+        if hit_enemies.is_empty() && !matches!(bullet.kind, ProjectileKind::Burst { .. }) {
+            continue;
+        }
+        for enemy_entity in hit_enemies {
+            let Ok((_, _, enemy, status_effects, boss)) = enemy_query.get_mut(enemy_entity) else {
+                continue;
+            };
+            damage_enemy.send(DamageEnemy {
+                target: enemy_entity,
+                amount: affinity_matrix.apply(bullet.damage, bullet.color.as_ref(), &enemy.color),
+                color: bullet.color.clone(),
+            });
+            // their damage over time would hurt an immune boss with a color it ignores
+            if boss.is_some_and(|boss| boss.is_immune_to(bullet.color.as_ref())) {
+                continue;
+            }
+            if let Some(mut status_effects) = status_effects {
+                for effect in bullet.effects.iter() {
+                    status_effects.apply(effect.clone());
+                }
+            }
+        }
+        commands.entity(bullet_entity).despawn();
     }
 }
 
@@ -80,3 +211,30 @@ fn break_down_bullets(mut commands: Commands, bullets_query: Query<Entity, With<
         commands.entity(entity).despawn();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intercepts_standing_target_directly() {
+        let time = intercept_time(Vec2::new(100., 0.), Vec2::ZERO, 50.);
+        assert!((time - 2.).abs() < 0.001);
+    }
+
+    #[test]
+    fn leads_moving_target() {
+        let offset = Vec2::new(100., 0.);
+        let velocity = Vec2::new(0., 30.);
+        let speed = 50.;
+        let time = intercept_time(offset, velocity, speed);
+        let meeting_point = offset + velocity * time;
+        assert!((meeting_point.length() - speed * time).abs() < 0.01);
+    }
+
+    #[test]
+    fn falls_back_to_direct_shot_if_target_is_too_fast() {
+        let time = intercept_time(Vec2::new(100., 0.), Vec2::new(200., 0.), 50.);
+        assert!((time - 2.).abs() < 0.001);
+    }
+}
@@ -0,0 +1,606 @@
+use crate::actions::Action;
+use crate::loading::{FontAssets, TextureAssets};
+use crate::map::{camera_position, spawn_map_tiles, tiles_to_string, Map, MapError, MapTile, Tile};
+use crate::ui::{ButtonColors, ButtonInteractions, GameState};
+use crate::{AppState, MAP_Z};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+const MIN_SIZE: usize = 3;
+const MAX_WIDTH: usize = 24;
+const MAX_HEIGHT: usize = 16;
+/// Exported maps can be used as the `map` of a mod
+#[cfg(not(target_arch = "wasm32"))]
+const EXPORT_PATH: &str = "maps/custom.txt";
+/// Brushes in the order of the toolbar, also selected with the number keys
+const BRUSHES: [(Tile, &str); 7] = [
+    (Tile::Path, "Path"),
+    (Tile::TowerPlot, "Plot"),
+    (Tile::Tower, "Tower"),
+    (Tile::Cloud, "Cloud"),
+    (Tile::Empty, "Empty"),
+    (Tile::Spawn, "Spawn"),
+    (Tile::Castle, "Castle"),
+];
+const SELECTED_BRUSH: Color = Color::rgb(0.35, 0.35, 0.15);
+
+/// Paint maps tile by tile, check them while drawing and play-test them right away
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Editor), setup_editor)
+            .add_systems(
+                Update,
+                (select_brush, click_editor_buttons, paint_tiles, show_editor)
+                    .chain()
+                    .run_if(in_state(AppState::Editor)),
+            )
+            .add_systems(OnExit(AppState::Editor), break_down_editor)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                spawn_play_test_button.run_if(resource_exists::<MapEditor>()),
+            )
+            .add_systems(
+                Update,
+                click_play_test_button.run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnExit(AppState::InGame), despawn_play_test_button);
+    }
+}
+
+/// Map being edited. Stays around while play-testing, so the editor can pick up where it left off.
+#[derive(Resource)]
+pub struct MapEditor {
+    /// Bottom row first, like [`Map::tiles`]
+    pub tiles: Vec<Vec<Tile>>,
+    pub tile_size: f32,
+    pub brush: Tile,
+    /// Result of validating the map after the last change
+    pub validation: Result<Map, MapError>,
+    /// Shown instead of the validation until the next change, like where the map was exported to
+    pub message: Option<String>,
+    /// Map of the game, put back when leaving the editor
+    original: Map,
+}
+
+impl MapEditor {
+    pub fn new(map: Map) -> Self {
+        MapEditor {
+            tiles: map.tiles.clone(),
+            tile_size: map.tile_size,
+            brush: Tile::Path,
+            validation: Ok(map.clone()),
+            message: None,
+            original: map,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.tiles.first().map_or(0, Vec::len)
+    }
+
+    pub fn height(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// The map in the text format [`Map::parse`] reads
+    pub fn to_map_string(&self) -> String {
+        tiles_to_string(&self.tiles)
+    }
+
+    /// Paint the brush onto a tile. Returns false if nothing changed.
+    pub fn paint(&mut self, column: usize, row: usize) -> bool {
+        let Some(current) = self.tiles.get(row).and_then(|tiles| tiles.get(column)) else {
+            return false;
+        };
+        if *current == self.brush {
+            return false;
+        }
+        // a map has a single spawn and castle, so placing one moves it
+        if self.brush == Tile::Spawn || self.brush == Tile::Castle {
+            for tile in self.tiles.iter_mut().flatten() {
+                if *tile == self.brush {
+                    *tile = Tile::Empty;
+                }
+            }
+        }
+        self.tiles[row][column] = self.brush.clone();
+        self.validate();
+        true
+    }
+
+    /// Columns are added and removed on the right, rows at the top. New tiles are empty.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let width = width.clamp(MIN_SIZE, MAX_WIDTH);
+        let height = height.clamp(MIN_SIZE, MAX_HEIGHT);
+        self.tiles.resize(height, vec![]);
+        for row in self.tiles.iter_mut() {
+            row.resize(width, Tile::Empty);
+        }
+        self.validate();
+    }
+
+    fn validate(&mut self) {
+        self.validation = Map::parse(&self.to_map_string());
+        self.message = None;
+    }
+
+    fn status(&self) -> (String, Color) {
+        match (&self.message, &self.validation) {
+            (Some(message), _) => (message.clone(), Color::rgb(0.9, 0.9, 0.9)),
+            (None, Ok(_)) => ("Valid map".to_string(), Color::rgb(0.5, 0.9, 0.5)),
+            (None, Err(error)) => (format!("Invalid: {}", error), Color::rgb(0.9, 0.4, 0.4)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum EditorCommand {
+    Brush(Tile),
+    AddColumn,
+    RemoveColumn,
+    AddRow,
+    RemoveRow,
+    Export,
+    PlayTest,
+    Back,
+}
+
+#[derive(Component)]
+struct EditorButton(EditorCommand);
+
+#[derive(Component)]
+struct EditorUi;
+
+#[derive(Component)]
+struct EditorStatus;
+
+#[derive(Component)]
+struct EditorTile;
+
+/// Leaves a play-test for the editor
+#[derive(Component)]
+struct PlayTestButton;
+
+fn setup_editor(
+    mut commands: Commands,
+    map: Res<Map>,
+    editor: Option<ResMut<MapEditor>>,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    tile_query: Query<Entity, With<MapTile>>,
+) {
+    for entity in tile_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    match editor {
+        // back from a play-test
+        Some(mut editor) => editor.set_changed(),
+        None => commands.insert_resource(MapEditor::new(map.clone())),
+    }
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::SpaceBetween,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(EditorUi)
+        .with_children(|parent| {
+            parent.spawn(toolbar()).with_children(|parent| {
+                for (index, (tile, label)) in BRUSHES.iter().enumerate() {
+                    spawn_button(
+                        parent,
+                        &format!("{} {}", index + 1, label),
+                        EditorCommand::Brush(tile.clone()),
+                        &font_assets,
+                        &button_colors,
+                    );
+                }
+            });
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ))
+                        .insert(EditorStatus);
+                    parent.spawn(toolbar()).with_children(|parent| {
+                        for (label, command) in [
+                            ("+ Column", EditorCommand::AddColumn),
+                            ("- Column", EditorCommand::RemoveColumn),
+                            ("+ Row", EditorCommand::AddRow),
+                            ("- Row", EditorCommand::RemoveRow),
+                            ("Export", EditorCommand::Export),
+                            ("Play-test", EditorCommand::PlayTest),
+                            ("Back", EditorCommand::Back),
+                        ] {
+                            spawn_button(parent, label, command, &font_assets, &button_colors);
+                        }
+                    });
+                });
+        });
+}
+
+fn toolbar() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.),
+            flex_wrap: FlexWrap::Wrap,
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    label: &str,
+    command: EditorCommand,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                padding: UiRect::all(Val::Px(8.)),
+                margin: UiRect::all(Val::Px(4.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            background_color: button_colors.normal.into(),
+            ..Default::default()
+        })
+        .insert(EditorButton(command))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 20.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
+fn select_brush(actions: Res<Input<Action>>, mut editor: ResMut<MapEditor>) {
+    for (index, (tile, _)) in BRUSHES.iter().enumerate() {
+        if actions.just_pressed(Action::Number(index as u8 + 1)) {
+            editor.brush = tile.clone();
+        }
+    }
+}
+
+fn click_editor_buttons(
+    mut commands: Commands,
+    button_colors: Res<ButtonColors>,
+    texture_assets: Res<TextureAssets>,
+    mut state: ResMut<NextState<AppState>>,
+    mut editor: ResMut<MapEditor>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &EditorButton),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => match &button.0 {
+                EditorCommand::Brush(tile) => editor.brush = tile.clone(),
+                EditorCommand::AddColumn => {
+                    let (width, height) = (editor.width() + 1, editor.height());
+                    editor.resize(width, height);
+                }
+                EditorCommand::RemoveColumn => {
+                    let (width, height) = (editor.width() - 1, editor.height());
+                    editor.resize(width, height);
+                }
+                EditorCommand::AddRow => {
+                    let (width, height) = (editor.width(), editor.height() + 1);
+                    editor.resize(width, height);
+                }
+                EditorCommand::RemoveRow => {
+                    let (width, height) = (editor.width(), editor.height() - 1);
+                    editor.resize(width, height);
+                }
+                EditorCommand::Export => {
+                    let message = export_map(&editor.to_map_string());
+                    editor.message = Some(message);
+                }
+                EditorCommand::PlayTest => match editor.validation.clone() {
+                    Ok(map) => {
+                        spawn_map_tiles(&mut commands, &map, &texture_assets);
+                        commands.insert_resource(map.gather_trees());
+                        commands.insert_resource(map);
+                        state.set(AppState::InGame);
+                    }
+                    Err(_) => editor.message = Some("Fix the map before play-testing".to_string()),
+                },
+                EditorCommand::Back => {
+                    commands.insert_resource(editor.original.gather_trees());
+                    commands.insert_resource(editor.original.clone());
+                    commands.remove_resource::<MapEditor>();
+                    state.set(AppState::Menu);
+                }
+            },
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = if button.0 == EditorCommand::Brush(editor.brush.clone()) {
+                    SELECTED_BRUSH.into()
+                } else {
+                    button_colors.normal.into()
+                };
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn export_map(map: &str) -> String {
+    let path = std::path::Path::new(EXPORT_PATH);
+    if let Some(folder) = path.parent() {
+        if let Err(error) = std::fs::create_dir_all(folder) {
+            warn!("Failed to create {}: {}", folder.display(), error);
+        }
+    }
+    match std::fs::write(path, map) {
+        Ok(()) => format!("Exported to {}", EXPORT_PATH),
+        Err(error) => {
+            warn!("Failed to export the map to {}: {}", EXPORT_PATH, error);
+            format!("Failed to export to {}", EXPORT_PATH)
+        }
+    }
+}
+
+/// The browser has no files to write to, so the map ends up in the console
+#[cfg(target_arch = "wasm32")]
+fn export_map(map: &str) -> String {
+    info!("Exported map:\n{}", map);
+    "Exported to the browser console".to_string()
+}
+
+fn paint_tiles(
+    actions: Res<Input<Action>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    button_query: Query<&Interaction, With<EditorButton>>,
+    mut editor: ResMut<MapEditor>,
+) {
+    if !actions.pressed(Action::PickUp)
+        || button_query
+            .iter()
+            .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) = (window.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    let Some(position) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+        .map(|ray| ray.origin.truncate())
+    else {
+        return;
+    };
+    let tile = (position / editor.tile_size).round();
+    if tile.x < 0. || tile.y < 0. {
+        return;
+    }
+    // only mark the editor as changed if the tile really changed, it is rendered again on change
+    if editor
+        .bypass_change_detection()
+        .paint(tile.x as usize, tile.y as usize)
+    {
+        editor.set_changed();
+    }
+}
+
+/// Draw the edited map and fit it into the window
+#[allow(clippy::too_many_arguments)]
+fn show_editor(
+    mut commands: Commands,
+    editor: Res<MapEditor>,
+    texture_assets: Res<TextureAssets>,
+    button_colors: Res<ButtonColors>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    tile_query: Query<Entity, With<EditorTile>>,
+    mut status_query: Query<&mut Text, With<EditorStatus>>,
+    mut button_query: Query<(&EditorButton, &Interaction, &mut BackgroundColor)>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    for entity in tile_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (row, tiles) in editor.tiles.iter().enumerate() {
+        for (column, tile) in tiles.iter().enumerate() {
+            commands
+                .spawn(SpriteBundle {
+                    texture: texture_assets.get_handle_for_tile(tile),
+                    transform: Transform::from_translation(Vec3::new(
+                        column as f32 * editor.tile_size,
+                        row as f32 * editor.tile_size,
+                        MAP_Z,
+                    )),
+                    ..Default::default()
+                })
+                .insert(EditorTile);
+        }
+    }
+    let (status, color) = editor.status();
+    for mut text in status_query.iter_mut() {
+        text.sections[0].value = status.clone();
+        text.sections[0].style.color = color;
+    }
+    for (button, interaction, mut background) in button_query.iter_mut() {
+        if let (EditorCommand::Brush(tile), Interaction::None) = (&button.0, interaction) {
+            *background = if *tile == editor.brush {
+                SELECTED_BRUSH.into()
+            } else {
+                button_colors.normal.into()
+            };
+        }
+    }
+    let size = Vec2::new(editor.width() as f32, editor.height() as f32) * editor.tile_size;
+    // leave room for the toolbars above and below the map
+    let scale = window.get_single().map_or(1., |window| {
+        (size.x / window.width())
+            .max((size.y + 3. * editor.tile_size) / window.height())
+            .max(1.)
+    });
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        let center = (size - editor.tile_size) / 2.;
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+        projection.scale = scale;
+    }
+}
+
+fn break_down_editor(
+    mut commands: Commands,
+    map: Res<Map>,
+    ui_query: Query<Entity, With<EditorUi>>,
+    tile_query: Query<Entity, With<EditorTile>>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    for entity in ui_query.iter().chain(tile_query.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        let position = camera_position(&map);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        projection.scale = 1.;
+    }
+}
+
+fn spawn_play_test_button(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+) {
+    commands
+        .spawn(ButtonBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.),
+                bottom: Val::Px(10.),
+                padding: UiRect::all(Val::Px(8.)),
+                ..Default::default()
+            },
+            background_color: button_colors.normal.into(),
+            ..Default::default()
+        })
+        .insert(PlayTestButton)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Back to editor",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 20.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
+fn click_play_test_button(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<NextState<AppState>>,
+    mut game_state: ResMut<GameState>,
+    mut interaction_query: ButtonInteractions<PlayTestButton>,
+) {
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                *game_state = GameState::default();
+                state.set(AppState::Editor);
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+fn despawn_play_test_button(
+    mut commands: Commands,
+    button_query: Query<Entity, With<PlayTestButton>>,
+) {
+    for entity in button_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placing_a_spawn_moves_it() {
+        let mut editor = MapEditor::new(Map::parse("a+q\n###").unwrap());
+        editor.brush = Tile::Spawn;
+        assert!(editor.paint(0, 0));
+        assert!(!editor.paint(0, 0));
+        assert_eq!(editor.to_map_string(), "#+q\na##");
+        assert_eq!(editor.validation.unwrap_err(), MapError::PathNotConnected);
+    }
+
+    #[test]
+    fn resizing_keeps_the_map_within_bounds() {
+        let mut editor = MapEditor::new(Map::load_map());
+        editor.resize(11, 11);
+        assert_eq!(editor.width(), 11);
+        assert_eq!(editor.validation.clone().unwrap_err(), MapError::Castles(0));
+        editor.resize(1, 100);
+        assert_eq!((editor.width(), editor.height()), (MIN_SIZE, MAX_HEIGHT));
+        assert!(editor.tiles[MAX_HEIGHT - 1]
+            .iter()
+            .all(|tile| *tile == Tile::Empty));
+    }
+
+    #[test]
+    fn fixed_maps_become_valid() {
+        let mut editor = MapEditor::new(Map::parse("a+q").unwrap());
+        editor.resize(3, 3);
+        assert!(editor.validation.is_ok());
+        editor.brush = Tile::Path;
+        editor.paint(0, 2);
+        assert_eq!(
+            editor.validation.clone().unwrap_err(),
+            MapError::UnusedPath { row: 1, column: 1 }
+        );
+        editor.brush = Tile::Cloud;
+        editor.paint(0, 2);
+        assert!(editor.validation.is_ok());
+    }
+}
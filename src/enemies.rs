@@ -1,40 +1,67 @@
 use bevy::prelude::*;
-use bevy::utils::Instant;
 use bevy_prototype_lyon::entity::ShapeBundle;
 use bevy_prototype_lyon::prelude::*;
 use rand::distributions::Standard;
 use rand::prelude::*;
+use serde::Deserialize;
 
-use crate::map::{Coordinate, Map};
+use crate::abilities::{Shield, Splitting, Sprinter};
+use crate::boss::{spawn_boss, Boss, Wildcard};
+use crate::forms::EnemyDefinitions;
+use crate::map::Map;
 use crate::puzzle::CurrentPiece;
+use crate::settings::{Palette, Settings};
+use crate::statistics::RunStatistics;
+use crate::status_effects::StatusEffects;
 use crate::ui::GameState;
+use crate::waves::Waves;
 use crate::{AppState, ENEMY_Z};
 
+const WAVE_CLEAR_GOLD: usize = 10;
+const HEALTH_BAR_WIDTH: f32 = 20.;
+/// Outline of enemy shapes, abilities like shields draw over it and restore it
+pub(crate) const ENEMY_STROKE_WIDTH: f32 = 2.;
+
 pub struct EnemiesPlugin;
 
 impl Plugin for EnemiesPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(WaveState {
-            last_spawn: Instant::now(),
-        })
-        .add_event::<EnemyBreach>()
-        .add_systems(
-            PostUpdate,
-            remove_enemies.run_if(in_state(AppState::InGame)),
-        )
-        .add_systems(
-            Update,
-            (
-                update_enemy_colors
-                    .in_set(EnemySet::UpdateColor)
-                    .after(EnemySet::Damage),
-                spawn_enemies.before(EnemySet::UpdateColor),
-                update_tamable_enemies.before(EnemySet::UpdateColor),
-                move_enemies.in_set(EnemySet::Move).before(EnemySet::Damage),
+        app.init_resource::<WaveState>()
+            .init_resource::<Waves>()
+            .init_resource::<EnemyDefinitions>()
+            .add_event::<EnemyBreach>()
+            .add_event::<DamageEnemy>()
+            .add_event::<EnemyDefeated>()
+            .add_event::<WaveStarted>()
+            .add_systems(OnEnter(AppState::InGame), reset_waves)
+            .add_systems(
+                PostUpdate,
+                remove_enemies.run_if(in_state(AppState::InGame)),
             )
-                .run_if(in_state(AppState::InGame)),
-        )
-        .add_systems(OnExit(AppState::InGame), break_down_enemies);
+            .add_systems(
+                Update,
+                (
+                    update_enemy_colors
+                        .in_set(EnemySet::UpdateColor)
+                        .after(EnemySet::Damage),
+                    spawn_enemies.before(EnemySet::UpdateColor),
+                    clear_waves,
+                    update_tamable_enemies
+                        .in_set(EnemySet::Move)
+                        .before(EnemySet::UpdateColor),
+                    move_enemies.in_set(EnemySet::Move).before(EnemySet::Damage),
+                    regenerate_enemies.before(EnemySet::Damage),
+                    draw_health_bars
+                        .after(EnemySet::ApplyDamage)
+                        .run_if(|settings: Res<Settings>| settings.health_bars),
+                    apply_damage
+                        .in_set(EnemySet::ApplyDamage)
+                        .after(EnemySet::Damage)
+                        .before(EnemySet::UpdateColor),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnExit(AppState::InGame), break_down_enemies);
     }
 }
 
@@ -42,15 +69,48 @@ impl Plugin for EnemiesPlugin {
 pub enum EnemySet {
     UpdateColor,
     Damage,
+    ApplyDamage,
     Move,
 }
 
 #[derive(Event)]
-pub struct EnemyBreach;
+pub struct EnemyBreach {
+    pub position: Vec2,
+}
+
+/// An enemy ran out of health and can now be tamed
+#[derive(Event)]
+pub struct EnemyDefeated;
+
+#[derive(Event)]
+pub struct WaveStarted;
+
+/// Damage dealt to an enemy. Shields and other defences are applied before it reaches [`Health`].
+#[derive(Event)]
+pub struct DamageEnemy {
+    pub target: Entity,
+    pub amount: i32,
+    /// Color of the tower dealing the damage
+    pub color: Option<EnemyColor>,
+}
 
 #[derive(Resource)]
 struct WaveState {
-    pub last_spawn: Instant,
+    spawn_timer: Timer,
+    current_wave: usize,
+    spawned: usize,
+    cleared: usize,
+}
+
+impl Default for WaveState {
+    fn default() -> Self {
+        WaveState {
+            spawn_timer: Timer::from_seconds(1., TimerMode::Repeating),
+            current_wave: 0,
+            spawned: 0,
+            cleared: 0,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -58,10 +118,12 @@ pub struct Tameable;
 
 #[derive(Clone, Component)]
 pub struct Enemy {
-    current_waypoint_index: usize,
+    pub(crate) current_waypoint_index: usize,
+    pub wave: usize,
     pub form: EnemyForm,
     pub color: EnemyColor,
-    pub bullets: Vec<Entity>,
+    /// Current movement in units per second
+    pub velocity: Vec2,
     pub colored_health: i32,
     pub travelled: f32,
     pub max_health: i32,
@@ -72,19 +134,60 @@ pub struct Health {
     pub value: i32,
 }
 
+/// Trees never move, so the closest one is looked up once per tile when the map is built
 #[derive(Resource)]
 pub struct Trees {
-    pub coordinates: Vec<Coordinate>,
+    pub tile_size: f32,
+    /// Closest tree for each tile, bottom row first like [`Map::tiles`]. Empty without trees.
+    pub closest: Vec<Vec<Vec2>>,
+}
+
+impl Trees {
+    /// Position of the tree closest to the tile under `position`, or the origin on a map without trees
+    pub fn closest(&self, position: Vec2) -> Vec2 {
+        let Some(width) = self.closest.first().map(Vec::len) else {
+            return Vec2::ZERO;
+        };
+        let tile = (position / self.tile_size).round().as_ivec2().clamp(
+            IVec2::ZERO,
+            IVec2::new(width as i32 - 1, self.closest.len() as i32 - 1),
+        );
+        self.closest[tile.y as usize][tile.x as usize]
+    }
 }
 
 impl Enemy {
-    pub fn get_color(&self, health: i32) -> Color {
+    pub fn new(form: EnemyForm, color: EnemyColor, health: i32) -> Self {
+        Enemy {
+            current_waypoint_index: 0,
+            wave: 0,
+            form,
+            max_health: health,
+            velocity: Vec2::ZERO,
+            colored_health: health,
+            color,
+            travelled: 0.,
+        }
+    }
+
+    /// Continue on the path from where the other enemy currently is
+    pub fn follow(&mut self, other: &Enemy) {
+        self.current_waypoint_index = other.current_waypoint_index;
+        self.travelled = other.travelled;
+        self.wave = other.wave;
+    }
+
+    pub fn gold_reward(&self) -> usize {
+        1 + self.max_health as usize / 20
+    }
+
+    pub fn get_color(&self, health: i32, palette: Palette) -> Color {
         let health_factor = if health > 0 {
             health as f32 / self.max_health as f32
         } else {
             0.
         };
-        Color::GRAY * health_factor + self.color.to_color() * (1. - health_factor)
+        Color::GRAY * health_factor + self.color.to_color(palette) * (1. - health_factor)
     }
 }
 
@@ -98,7 +201,7 @@ impl Distribution<EnemyForm> for Standard {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
 pub enum EnemyColor {
     Red,
     Lilac,
@@ -120,7 +223,16 @@ impl Distribution<EnemyColor> for Standard {
 }
 
 impl EnemyColor {
-    pub fn to_color(&self) -> Color {
+    pub fn to_color(&self, palette: Palette) -> Color {
+        if palette == Palette::Colorblind {
+            return match self {
+                EnemyColor::Lilac => Color::rgb(240. / 255., 228. / 255., 66. / 255.),
+                EnemyColor::Red => Color::rgb(213. / 255., 94. / 255., 0.),
+                EnemyColor::Green => Color::rgb(0., 158. / 255., 115. / 255.),
+                EnemyColor::Pink => Color::rgb(204. / 255., 121. / 255., 167. / 255.),
+                EnemyColor::Blue => Color::rgb(0., 114. / 255., 178. / 255.),
+            };
+        }
         match self {
             EnemyColor::Lilac => Color::rgb(84. / 255., 13. / 255., 110. / 255.),
             EnemyColor::Red => Color::rgb(235. / 255., 66. / 255., 102. / 255.),
@@ -131,28 +243,41 @@ impl EnemyColor {
     }
 }
 
+fn reset_waves(mut wave_state: ResMut<WaveState>) {
+    *wave_state = WaveState::default();
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_enemies(
     mut commands: Commands,
     map: Res<Map>,
     time: Res<Time>,
+    waves: Res<Waves>,
     mut game_state: ResMut<GameState>,
     mut wave_state: ResMut<WaveState>,
+    definitions: Res<EnemyDefinitions>,
+    mut wave_started: EventWriter<WaveStarted>,
 ) {
-    if time.last_update().is_some()
-        && time
-            .last_update()
-            .unwrap()
-            .duration_since(wave_state.last_spawn)
-            .as_secs_f32()
-            < 1.
-    {
+    if !wave_state.spawn_timer.tick(time.delta()).just_finished() {
         return;
-    } else if time.last_update().is_some() {
-        wave_state.last_spawn = time.last_update().unwrap();
     }
     if game_state.health < 1 {
         return;
     }
+    let Some(wave) = waves.get(wave_state.current_wave) else {
+        return;
+    };
+    if wave_state.spawned >= wave.enemies + wave.boss.is_some() as usize {
+        wave_state.current_wave += 1;
+        wave_state.spawned = 0;
+    }
+    let Some(wave) = waves.get(wave_state.current_wave) else {
+        return;
+    };
+    wave_state.spawned += 1;
+    if wave_state.spawned == 1 {
+        wave_started.send(WaveStarted);
+    }
     game_state.enemy_health += 1;
     let form: EnemyForm = random();
     let color: EnemyColor = random();
@@ -161,36 +286,64 @@ fn spawn_enemies(
     let mut rng = rand::thread_rng();
     let percent: i32 = rng.gen_range(0..50); // generates a float between 0 and 1
     health += percent * one_percent;
-    create_enemy(&mut commands, color, &map, health, form);
+    let health = definitions.traits(&form).spawn_health(health);
+    let transform = Transform::from_translation(map.spawn.to_translation(ENEMY_Z));
+    let mut enemy = Enemy::new(form, color, health);
+    enemy.wave = wave_state.current_wave;
+    if wave_state.spawned > wave.enemies {
+        if let Some(boss) = wave.boss.as_ref() {
+            spawn_boss(&mut commands, boss, enemy, transform);
+        }
+        return;
+    }
+    let entity = create_enemy(&mut commands, enemy, transform);
+    let mut entity = commands.entity(entity);
+    for ability_chance in wave.abilities.iter() {
+        if rng.gen_bool(ability_chance.chance) {
+            ability_chance.ability.insert(&mut entity, health);
+        }
+    }
 }
 
-fn create_enemy(
-    commands: &mut Commands,
-    color: EnemyColor,
-    map: &Res<Map>,
-    health: i32,
-    form: EnemyForm,
+/// A wave is cleared once all its enemies are spawned and none of them is on the way to the castle
+fn clear_waves(
+    waves: Res<Waves>,
+    mut game_state: ResMut<GameState>,
+    mut wave_state: ResMut<WaveState>,
+    mut statistics: ResMut<RunStatistics>,
+    enemy_query: Query<&Enemy, Without<Tameable>>,
 ) {
-    let enemy = Enemy {
-        current_waypoint_index: 0,
-        form: form.clone(),
-        max_health: health,
-        bullets: vec![],
-        colored_health: health,
-        color,
-        travelled: 0.,
-    };
+    while wave_state.cleared < wave_state.current_wave {
+        let wave = wave_state.cleared;
+        if enemy_query.iter().any(|enemy| enemy.wave == wave) {
+            return;
+        }
+        wave_state.cleared += 1;
+        if game_state.health > 0 {
+            // repeated waves pay like the last defined one
+            let reward = WAVE_CLEAR_GOLD + waves.definition_index(wave) * 5;
+            game_state.gold += reward;
+            statistics.gold_earned += reward;
+            statistics.waves_cleared += 1;
+        }
+    }
+}
+
+pub fn create_enemy(commands: &mut Commands, enemy: Enemy, transform: Transform) -> Entity {
+    let health = enemy.max_health;
     commands
-        .spawn(form.build_bundle(
-            Transform::from_translation(Vec3::new(map.spawn.x, map.spawn.y, ENEMY_Z)),
-            enemy.get_color(health),
-            Some(enemy.get_color(health)),
-        ))
+        .spawn(
+            enemy
+                .form
+                .build_bundle(transform, Color::GRAY, Some(Color::GRAY)),
+        )
         .insert(enemy)
-        .insert(Health { value: health });
+        .insert(Health { value: health })
+        .insert(StatusEffects::default())
+        .id()
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
 pub enum EnemyForm {
     Circle,
     Triangle,
@@ -198,59 +351,100 @@ pub enum EnemyForm {
 }
 
 impl EnemyForm {
+    pub fn sides(&self) -> usize {
+        match self {
+            EnemyForm::Circle => 5,
+            EnemyForm::Triangle => 3,
+            EnemyForm::Quadratic => 4,
+        }
+    }
+
     pub fn build_bundle(
         &self,
         transform: Transform,
         outline_color: Color,
         fill_color: Option<Color>,
     ) -> impl Bundle {
-        let shape = shapes::RegularPolygon {
-            sides: match self {
-                EnemyForm::Circle => 5,
-                EnemyForm::Triangle => 3,
-                EnemyForm::Quadratic => 4,
-            },
-            feature: shapes::RegularPolygonFeature::Radius(12.0),
-            ..shapes::RegularPolygon::default()
-        };
+        polygon_bundle(self.sides(), 12., transform, outline_color, fill_color)
+    }
 
-        (
-            ShapeBundle {
-                path: GeometryBuilder::build_as(&shape),
-                transform,
-                ..default()
-            },
-            Fill::color(fill_color.unwrap_or(Color::NONE)),
-            Stroke::new(outline_color, 2.0),
-        )
+    /// Bosses are a lot bigger and have three times the sides of their form
+    pub fn build_boss_bundle(
+        &self,
+        transform: Transform,
+        outline_color: Color,
+        fill_color: Option<Color>,
+    ) -> impl Bundle {
+        polygon_bundle(self.sides() * 3, 28., transform, outline_color, fill_color)
     }
 }
 
+fn polygon_bundle(
+    sides: usize,
+    radius: f32,
+    transform: Transform,
+    outline_color: Color,
+    fill_color: Option<Color>,
+) -> impl Bundle {
+    let shape = shapes::RegularPolygon {
+        sides,
+        feature: shapes::RegularPolygonFeature::Radius(radius),
+        ..shapes::RegularPolygon::default()
+    };
+
+    (
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shape),
+            transform,
+            ..default()
+        },
+        Fill::color(fill_color.unwrap_or(Color::NONE)),
+        Stroke::new(outline_color, ENEMY_STROKE_WIDTH),
+    )
+}
+
+/// An enemy that might leave the map, by breaking through or being beaten
+type LeavingEnemy = (
+    Entity,
+    &'static Enemy,
+    &'static Health,
+    &'static Transform,
+    Option<&'static Splitting>,
+    Option<&'static Boss>,
+);
+
 fn remove_enemies(
     mut commands: Commands,
     map: Res<Map>,
     mut game_state: ResMut<GameState>,
+    mut statistics: ResMut<RunStatistics>,
     mut enemy_breach: EventWriter<EnemyBreach>,
-    mut enemy_query: Query<(Entity, &mut Enemy, &Health), Without<Tameable>>,
+    mut enemy_defeated: EventWriter<EnemyDefeated>,
+    enemy_query: Query<LeavingEnemy, Without<Tameable>>,
 ) {
-    for (entity, mut enemy, health) in enemy_query.iter_mut() {
+    for (entity, enemy, health, transform, splitting, boss) in enemy_query.iter() {
         if health.value < 0 {
             if game_state.health > 0 {
                 game_state.score += enemy.max_health as usize;
+                game_state.gold += enemy.gold_reward();
+                statistics.gold_earned += enemy.gold_reward();
             }
             commands.entity(entity).insert(Tameable);
-            for id in enemy.bullets.drain(..) {
-                commands.entity(id).despawn();
+            enemy_defeated.send(EnemyDefeated);
+            if boss.is_some() {
+                commands.entity(entity).insert(Wildcard);
+            }
+            if let Some(splitting) = splitting {
+                split_enemy(&mut commands, enemy, transform, splitting.pieces);
             }
             continue;
         }
         if enemy.current_waypoint_index >= map.waypoints.len() {
             if game_state.health > 0 {
                 game_state.health -= 1;
-                enemy_breach.send(EnemyBreach);
-            }
-            for id in enemy.bullets.drain(..) {
-                commands.entity(id).despawn();
+                enemy_breach.send(EnemyBreach {
+                    position: transform.translation.truncate(),
+                });
             }
             commands.entity(entity).despawn();
             continue;
@@ -258,17 +452,89 @@ fn remove_enemies(
     }
 }
 
-fn move_enemies(
+fn split_enemy(commands: &mut Commands, enemy: &Enemy, transform: &Transform, pieces: usize) {
+    for piece in 0..pieces {
+        let mut smaller_enemy = Enemy::new(
+            enemy.form.clone(),
+            enemy.color.clone(),
+            (enemy.max_health / 2).max(1),
+        );
+        smaller_enemy.follow(enemy);
+        smaller_enemy.travelled -= piece as f32 * 10.;
+        let mut transform = *transform;
+        transform.scale *= 0.6;
+        transform.translation.x += piece as f32 * 6.;
+        create_enemy(commands, smaller_enemy, transform);
+    }
+}
+
+/// An enemy taking damage and what can protect it
+type DamagedEnemy = (
+    &'static Enemy,
+    &'static mut Health,
+    Option<&'static mut Shield>,
+    Option<&'static Boss>,
+);
+
+fn apply_damage(
+    definitions: Res<EnemyDefinitions>,
+    mut damage_reader: EventReader<DamageEnemy>,
+    mut enemy_query: Query<DamagedEnemy, Without<Tameable>>,
+) {
+    for damage in damage_reader.iter() {
+        let Ok((enemy, mut health, shield, boss)) = enemy_query.get_mut(damage.target) else {
+            continue;
+        };
+        if boss.is_some_and(|boss| boss.is_immune_to(damage.color.as_ref())) {
+            continue;
+        }
+        let mut amount = definitions.traits(&enemy.form).mitigate(damage.amount);
+        if let Some(mut shield) = shield {
+            let absorbed = amount.min(shield.value);
+            shield.value -= absorbed;
+            amount -= absorbed;
+        }
+        if amount > 0 {
+            health.value -= amount;
+        }
+    }
+}
+
+/// An enemy walking the path and what changes its pace
+type WalkingEnemy = (
+    &'static mut Enemy,
+    &'static mut Transform,
+    Option<&'static mut Sprinter>,
+    Option<&'static Boss>,
+    Option<&'static StatusEffects>,
+);
+
+pub(crate) fn move_enemies(
     time: Res<Time>,
     map: Res<Map>,
-    mut enemy_query: Query<(&mut Enemy, &mut Transform), Without<Tameable>>,
+    definitions: Res<EnemyDefinitions>,
+    mut enemy_query: Query<WalkingEnemy, Without<Tameable>>,
 ) {
     let delta = time.delta().as_millis() as f32;
-    let speed = 0.1;
-    for (mut enemy, mut transform) in enemy_query.iter_mut() {
+    for (mut enemy, mut transform, sprinter, boss, status_effects) in enemy_query.iter_mut() {
         if enemy.current_waypoint_index >= map.waypoints.len() {
             continue;
         }
+        let mut speed = 0.1 * definitions.traits(&enemy.form).speed;
+        if let Some(mut sprinter) = sprinter {
+            sprinter.tick(time.delta());
+            speed *= sprinter.speed_multiplier();
+        }
+        if let Some(boss) = boss {
+            speed *= boss.speed_multiplier;
+        }
+        if let Some(status_effects) = status_effects {
+            speed *= status_effects.speed_multiplier();
+        }
+        if speed <= 0. {
+            enemy.velocity = Vec2::ZERO;
+            continue;
+        }
         let destination = map.waypoints.get(enemy.current_waypoint_index).unwrap();
         let mut distance = Vec3::new(destination.x, destination.y, ENEMY_Z) - transform.translation;
         distance.z = 0.;
@@ -277,6 +543,7 @@ fn move_enemies(
             continue;
         }
         let movement = distance.normalize() * delta * speed;
+        enemy.velocity = distance.truncate().normalize() * speed * 1000.;
         if movement.length() > distance.length() {
             transform.translation = Vec3::new(destination.x, destination.y, ENEMY_Z);
             enemy.travelled += distance.length();
@@ -288,15 +555,52 @@ fn move_enemies(
     }
 }
 
+fn regenerate_enemies(
+    time: Res<Time>,
+    definitions: Res<EnemyDefinitions>,
+    mut since_last_regeneration: Local<f32>,
+    mut enemy_query: Query<(&Enemy, &mut Health), Without<Tameable>>,
+) {
+    *since_last_regeneration += time.delta_seconds();
+    if *since_last_regeneration < 1. {
+        return;
+    }
+    *since_last_regeneration -= 1.;
+    for (enemy, mut health) in enemy_query.iter_mut() {
+        let regeneration = definitions.traits(&enemy.form).regeneration;
+        if regeneration == 0 || health.value >= enemy.max_health {
+            continue;
+        }
+        health.value = (health.value + regeneration).min(enemy.max_health);
+    }
+}
+
 fn update_enemy_colors(
+    settings: Res<Settings>,
     mut damaged_enemies: Query<(&mut Fill, &mut Stroke, &Health, &Enemy), Changed<Health>>,
 ) {
     for (mut fill, mut stroke, health, enemy) in damaged_enemies.iter_mut() {
         if health.value == enemy.colored_health {
             continue;
         }
-        fill.color = enemy.get_color(health.value);
-        stroke.color = enemy.get_color(health.value);
+        fill.color = enemy.get_color(health.value, settings.palette);
+        stroke.color = enemy.get_color(health.value, settings.palette);
+    }
+}
+
+fn draw_health_bars(
+    mut gizmos: Gizmos,
+    enemy_query: Query<(&Transform, &Health, &Enemy), Without<Tameable>>,
+) {
+    for (transform, health, enemy) in enemy_query.iter() {
+        let start = transform.translation.truncate() + Vec2::new(-HEALTH_BAR_WIDTH / 2., 16.);
+        let fraction = (health.value as f32 / enemy.max_health as f32).clamp(0., 1.);
+        gizmos.line_2d(start, start + Vec2::X * HEALTH_BAR_WIDTH, Color::DARK_GRAY);
+        gizmos.line_2d(
+            start,
+            start + Vec2::X * HEALTH_BAR_WIDTH * fraction,
+            Color::LIME_GREEN,
+        );
     }
 }
 
@@ -315,22 +619,8 @@ fn update_tamable_enemies(
                 continue;
             }
         }
-        let (_, closest_tree_position) = trees.coordinates.iter().fold(
-            (10_000., Coordinate { x: 0., y: 0. }),
-            |acc, coordinate| {
-                let (old_distance, _) = acc;
-                let distance = (Vec3::new(coordinate.x, coordinate.y, ENEMY_Z)
-                    - transform.translation)
-                    .length();
-                if distance < old_distance {
-                    (distance, coordinate.clone())
-                } else {
-                    acc
-                }
-            },
-        );
-        let direction = Vec3::new(closest_tree_position.x, closest_tree_position.y, ENEMY_Z)
-            - transform.translation;
+        let closest_tree = trees.closest(transform.translation.truncate());
+        let direction = closest_tree.extend(ENEMY_Z) - transform.translation;
         if direction.is_finite() {
             let movement = direction.normalize() * delta * speed;
             if movement.length() > direction.length() {
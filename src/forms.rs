@@ -0,0 +1,147 @@
+use crate::enemies::EnemyForm;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+/// Defensive traits of every enemy form. Used when spawning, moving and damaging enemies.
+pub static FORM_TRAITS: [(EnemyForm, FormTraits); 3] = [
+    (
+        EnemyForm::Circle,
+        FormTraits {
+            armor: 0,
+            speed: 1.,
+            regeneration: 2,
+            health: 1.,
+        },
+    ),
+    (
+        EnemyForm::Triangle,
+        FormTraits {
+            armor: 0,
+            speed: 1.5,
+            regeneration: 0,
+            health: 0.8,
+        },
+    ),
+    (
+        EnemyForm::Quadratic,
+        FormTraits {
+            armor: 5,
+            speed: 0.8,
+            regeneration: 0,
+            health: 1.,
+        },
+    ),
+];
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct FormTraits {
+    /// Subtracted from every hit. A hit always deals at least one damage.
+    pub armor: i32,
+    /// Factor for the movement speed
+    pub speed: f32,
+    /// Health restored per second
+    pub regeneration: i32,
+    /// Factor for the health of newly spawned enemies
+    pub health: f32,
+}
+
+impl FormTraits {
+    pub fn mitigate(&self, damage: i32) -> i32 {
+        if damage <= 0 {
+            return 0;
+        }
+        (damage - self.armor).max(1)
+    }
+
+    pub fn spawn_health(&self, health: i32) -> i32 {
+        ((health as f32 * self.health).round() as i32).max(1)
+    }
+}
+
+impl EnemyForm {
+    pub fn traits(&self) -> &'static FormTraits {
+        FORM_TRAITS
+            .iter()
+            .find(|(form, _)| form == self)
+            .map(|(_, traits)| traits)
+            .unwrap()
+    }
+}
+
+/// Traits of the forms in the current game. Mods can replace the built-in [`FORM_TRAITS`].
+#[derive(Clone, Resource)]
+pub struct EnemyDefinitions {
+    traits: HashMap<EnemyForm, FormTraits>,
+}
+
+impl Default for EnemyDefinitions {
+    fn default() -> Self {
+        EnemyDefinitions {
+            traits: FORM_TRAITS.iter().cloned().collect(),
+        }
+    }
+}
+
+impl EnemyDefinitions {
+    pub fn traits(&self, form: &EnemyForm) -> &FormTraits {
+        self.traits.get(form).unwrap_or_else(|| form.traits())
+    }
+
+    pub fn insert(&mut self, form: EnemyForm, traits: FormTraits) {
+        self.traits.insert(form, traits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_form_has_traits() {
+        for form in [EnemyForm::Circle, EnemyForm::Triangle, EnemyForm::Quadratic] {
+            assert_eq!(
+                FORM_TRAITS
+                    .iter()
+                    .filter(|(other, _)| other == &form)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn quadratics_are_armored() {
+        let traits = EnemyForm::Quadratic.traits();
+        assert_eq!(traits.mitigate(15), 10);
+        assert_eq!(EnemyForm::Circle.traits().mitigate(15), 15);
+    }
+
+    #[test]
+    fn armor_never_blocks_a_hit_completely() {
+        let traits = EnemyForm::Quadratic.traits();
+        assert_eq!(traits.mitigate(3), 1);
+        assert_eq!(traits.mitigate(0), 0);
+        assert_eq!(traits.mitigate(-4), 0);
+    }
+
+    #[test]
+    fn triangles_are_the_fastest() {
+        let triangle_speed = EnemyForm::Triangle.traits().speed;
+        assert!(triangle_speed > EnemyForm::Circle.traits().speed);
+        assert!(triangle_speed > EnemyForm::Quadratic.traits().speed);
+    }
+
+    #[test]
+    fn only_circles_regenerate() {
+        assert!(EnemyForm::Circle.traits().regeneration > 0);
+        assert_eq!(EnemyForm::Triangle.traits().regeneration, 0);
+        assert_eq!(EnemyForm::Quadratic.traits().regeneration, 0);
+    }
+
+    #[test]
+    fn spawn_health_is_at_least_one() {
+        assert_eq!(EnemyForm::Triangle.traits().spawn_health(10), 8);
+        assert_eq!(EnemyForm::Triangle.traits().spawn_health(1), 1);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::actions::{follow_focus, Action, Focus, VirtualCursor};
+use crate::enemies::Tameable;
+use crate::map::Map;
+use crate::puzzle::{update_pick_source, PickSource, PuzzleSlot, Puzzles};
+use crate::AppState;
+use bevy::prelude::*;
+
+/// Keyboard play without a mouse: step over tiles, jump to plots and select puzzle slots
+pub struct GridCursorPlugin;
+
+impl Plugin for GridCursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_grid_cursor,
+                move_grid_cursor,
+                jump_to_plots,
+                select_slots,
+            )
+                .chain()
+                .after(follow_focus)
+                .before(update_pick_source)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+/// Map tile closest to the position
+fn tile_of(position: Vec2, map: &Map) -> IVec2 {
+    let tile = (position / map.tile_size).round().as_ivec2();
+    tile.clamp(
+        IVec2::ZERO,
+        IVec2::new(map.width as i32 - 1, map.height as i32 - 1),
+    )
+}
+
+fn toggle_grid_cursor(
+    actions: Res<Input<Action>>,
+    map: Res<Map>,
+    pick_source: Res<PickSource>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+) {
+    if !actions.just_pressed(Action::ToggleGrid) {
+        return;
+    }
+    virtual_cursor.grid = !virtual_cursor.grid;
+    if !virtual_cursor.grid {
+        virtual_cursor.focus = Focus::Free;
+        return;
+    }
+    let position = if virtual_cursor.active {
+        virtual_cursor.position
+    } else {
+        pick_source.last_cursor_pos
+    };
+    virtual_cursor.position = tile_of(position, &map).as_vec2() * map.tile_size;
+    virtual_cursor.active = true;
+    virtual_cursor.focus = Focus::Tile;
+}
+
+/// Step to the neighbouring tile and snap onto a tameable enemy on it
+fn move_grid_cursor(
+    actions: Res<Input<Action>>,
+    map: Res<Map>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+    tameable_query: Query<(Entity, &Transform), With<Tameable>>,
+) {
+    if !virtual_cursor.grid {
+        return;
+    }
+    let mut step = IVec2::ZERO;
+    for (action, direction) in [
+        (Action::CursorUp, IVec2::Y),
+        (Action::CursorDown, IVec2::NEG_Y),
+        (Action::CursorLeft, IVec2::NEG_X),
+        (Action::CursorRight, IVec2::X),
+    ] {
+        if actions.just_pressed(action) {
+            step += direction;
+        }
+    }
+    if step == IVec2::ZERO {
+        return;
+    }
+    let center = step_tile(virtual_cursor.position, step, &map).as_vec2() * map.tile_size;
+    virtual_cursor.active = true;
+    virtual_cursor.position = center;
+    virtual_cursor.focus = Focus::Tile;
+    let tameables = tameable_query
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate()));
+    if let Some((entity, position)) = snap_target(center, map.tile_size, tameables) {
+        virtual_cursor.position = position;
+        virtual_cursor.focus = Focus::Tameable(entity);
+    }
+}
+
+/// Tile next to the one closest to the position
+fn step_tile(position: Vec2, step: IVec2, map: &Map) -> IVec2 {
+    tile_of(position + step.as_vec2() * map.tile_size, map)
+}
+
+/// The candidate on the tile around the center that is closest to it
+fn snap_target(
+    center: Vec2,
+    tile_size: f32,
+    candidates: impl IntoIterator<Item = (Entity, Vec2)>,
+) -> Option<(Entity, Vec2)> {
+    candidates
+        .into_iter()
+        .filter(|(_, position)| (*position - center).abs().max_element() < tile_size / 2.)
+        .min_by(|(_, a), (_, b)| a.distance(center).total_cmp(&b.distance(center)))
+}
+
+/// Number keys jump to the tower plots, 1 to 9 and then 0 for the tenth
+fn jump_to_plots(
+    actions: Res<Input<Action>>,
+    map: Res<Map>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+) {
+    let Some(number) = (0..=9).find(|number| actions.just_pressed(Action::Number(*number))) else {
+        return;
+    };
+    if let Some(plot) = map.plot_coordinates().get(plot_index(number)) {
+        virtual_cursor.position = Vec2::new(plot.x, plot.y);
+        virtual_cursor.active = true;
+        virtual_cursor.focus = Focus::Tile;
+    }
+}
+
+/// Slot keys select a slot of the puzzle on the plot under the cursor
+fn select_slots(
+    actions: Res<Input<Action>>,
+    puzzles: Res<Puzzles>,
+    slot_query: Query<(&Transform, &PuzzleSlot)>,
+    mut virtual_cursor: ResMut<VirtualCursor>,
+) {
+    let Some(number) = (1..=4).find(|number| actions.just_pressed(Action::Slot(*number))) else {
+        return;
+    };
+    if virtual_cursor.focus != Focus::Tile {
+        return;
+    }
+    let position = virtual_cursor.position;
+    let slot = puzzles
+        .towers
+        .iter()
+        .find(|puzzle| Vec2::new(puzzle.coordinate.x, puzzle.coordinate.y) == position)
+        .and_then(|puzzle| {
+            slot_query
+                .iter()
+                .find(|(_, slot)| slot.puzzle_id == puzzle.id && slot.index + 1 == number as usize)
+        });
+    if let Some((transform, _)) = slot {
+        virtual_cursor.position = transform.translation.truncate();
+        virtual_cursor.focus = Focus::Slot;
+    }
+}
+
+/// Index into the plots for a number key; 0 comes after 9 like on the keyboard
+fn plot_index(number: u8) -> usize {
+    (number as usize + 9) % 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_snap_to_the_closest_tile() {
+        let map = Map::load_map();
+        let size = map.tile_size;
+        assert_eq!(tile_of(Vec2::new(0.4, 0.4) * size, &map), IVec2::ZERO);
+        assert_eq!(tile_of(Vec2::new(2.6, 1.4) * size, &map), IVec2::new(3, 1));
+        assert_eq!(tile_of(Vec2::splat(-3.) * size, &map), IVec2::ZERO);
+        assert_eq!(
+            tile_of(Vec2::splat(100.) * size, &map),
+            IVec2::new(map.width as i32 - 1, map.height as i32 - 1)
+        );
+    }
+
+    #[test]
+    fn steps_stop_at_the_map_edge() {
+        let map = Map::load_map();
+        let size = map.tile_size;
+        assert_eq!(step_tile(Vec2::ZERO, IVec2::X, &map), IVec2::X);
+        assert_eq!(step_tile(Vec2::ZERO, IVec2::NEG_Y, &map), IVec2::ZERO);
+        // a cursor resting on an enemy between tiles steps from its closest tile
+        assert_eq!(
+            step_tile(Vec2::new(1.3, 2.) * size, IVec2::Y, &map),
+            IVec2::new(1, 3)
+        );
+    }
+
+    #[test]
+    fn snapping_picks_the_closest_enemy_on_the_tile() {
+        let map = Map::load_map();
+        let size = map.tile_size;
+        let center = Vec2::new(2., 2.) * size;
+        let near = Entity::from_raw(1);
+        let far = Entity::from_raw(2);
+        let outside = Entity::from_raw(3);
+        let candidates = [
+            (far, center + Vec2::new(0.4, 0.) * size),
+            (near, center + Vec2::new(0., 0.1) * size),
+            (outside, center + Vec2::new(0., 0.6) * size),
+        ];
+        assert_eq!(
+            snap_target(center, size, candidates).map(|(entity, _)| entity),
+            Some(near)
+        );
+        assert_eq!(snap_target(center, size, candidates[2..].to_vec()), None);
+    }
+
+    #[test]
+    fn zero_jumps_to_the_tenth_plot() {
+        assert_eq!(plot_index(1), 0);
+        assert_eq!(plot_index(9), 8);
+        assert_eq!(plot_index(0), 9);
+        assert!(Map::load_map().plot_coordinates().len() >= 10);
+    }
+}
@@ -1,25 +1,55 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::plugin::ShapePlugin;
 
+use crate::abilities::AbilitiesPlugin;
+use crate::actions::ActionsPlugin;
 use crate::audio::InternalAudioPlugin;
+use crate::boss::BossPlugin;
+use crate::bot::BotPlugin;
 use crate::bullets::BulletPlugin;
+use crate::editor::EditorPlugin;
 use crate::enemies::EnemiesPlugin;
+use crate::grid_cursor::GridCursorPlugin;
 use crate::loading::LoadingPlugin;
 use crate::map::MapPlugin;
 use crate::menu::MenuPlugin;
+use crate::mods::ModsPlugin;
+use crate::music::MusicPlugin;
 use crate::puzzle::PuzzlePlugin;
+use crate::settings::SettingsPlugin;
+use crate::spatial::SpatialPlugin;
+use crate::statistics::StatisticsPlugin;
+use crate::status_effects::StatusEffectsPlugin;
 use crate::towers::TowersPlugin;
 use crate::ui::UiPlugin;
 
+mod abilities;
+mod actions;
+mod affinity;
 mod audio;
+mod boss;
+pub mod bot;
 mod bullets;
+pub mod editor;
 mod enemies;
+mod forms;
+mod grid_cursor;
 mod loading;
 mod map;
 mod menu;
+pub mod mods;
+mod music;
 mod puzzle;
+mod recipes;
+pub mod settings;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+pub mod spatial;
+mod statistics;
+mod status_effects;
 mod towers;
 mod ui;
+mod waves;
 
 pub struct GamePlugin;
 
@@ -28,6 +58,7 @@ pub const TOWER_Z: f32 = 1.;
 pub const PUZZLE_Z: f32 = 2.;
 pub const ENEMY_Z: f32 = 3.;
 pub const BULLET_Z: f32 = 4.;
+pub const CURSOR_Z: f32 = 5.;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 pub enum AppState {
@@ -36,6 +67,8 @@ pub enum AppState {
     #[default]
     Loading,
     Menu,
+    /// Painting and play-testing maps
+    Editor,
 }
 
 impl Plugin for GamePlugin {
@@ -48,11 +81,25 @@ impl Plugin for GamePlugin {
                 MenuPlugin,
                 MapPlugin,
                 EnemiesPlugin,
+                AbilitiesPlugin,
+                BossPlugin,
+                StatusEffectsPlugin,
                 TowersPlugin,
                 BulletPlugin,
                 UiPlugin,
                 PuzzlePlugin,
                 InternalAudioPlugin,
+            ))
+            .add_plugins((
+                StatisticsPlugin,
+                SpatialPlugin,
+                BotPlugin,
+                ActionsPlugin,
+                GridCursorPlugin,
+                SettingsPlugin,
+                MusicPlugin,
+                ModsPlugin,
+                EditorPlugin,
             ));
         app.add_systems(OnEnter(AppState::Restart), switch_to_game);
     }
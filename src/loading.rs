@@ -1,22 +1,128 @@
 use crate::map::Tile;
+use crate::settings::Settings;
 use crate::AppState;
+use bevy::asset::LoadState;
 use bevy::prelude::*;
+use bevy_asset_loader::dynamic_asset::DynamicAsset;
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::AudioSource;
 
+pub const DEFAULT_THEME: &str = "default";
+#[cfg(not(target_arch = "wasm32"))]
+const THEMES_DIRECTORY: &str = "assets/themes";
+const MANIFEST_EXTENSION: &str = ".assets.ron";
+
 pub struct LoadingPlugin;
 
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_loading_state(
-            LoadingState::new(AppState::Loading).continue_to_state(AppState::Menu),
-        )
-        .add_collection_to_loading_state::<_, FontAssets>(AppState::Loading)
-        .add_collection_to_loading_state::<_, AudioAssets>(AppState::Loading)
-        .add_collection_to_loading_state::<_, TextureAssets>(AppState::Loading);
+        let theme = app
+            .world
+            .get_resource::<Settings>()
+            .map(|settings| settings.theme.clone())
+            .unwrap_or_else(|| Settings::load().theme);
+        let manifest = theme_manifest(&theme);
+        app.insert_resource(ThemeManifest(manifest.clone()))
+            .add_loading_state(
+                LoadingState::new(AppState::Loading).continue_to_state(AppState::Menu),
+            )
+            .add_dynamic_collection_to_loading_state::<_, StandardDynamicAssetCollection>(
+                AppState::Loading,
+                &manifest,
+            )
+            .add_collection_to_loading_state::<_, FontAssets>(AppState::Loading)
+            .add_collection_to_loading_state::<_, AudioAssets>(AppState::Loading)
+            .add_collection_to_loading_state::<_, TextureAssets>(AppState::Loading)
+            .add_systems(
+                OnEnter(AppState::Loading),
+                (track_assets, spawn_loading_screen),
+            )
+            .add_systems(
+                Update,
+                (update_loading_progress, show_loading_progress)
+                    .chain()
+                    .run_if(in_state(AppState::Loading)),
+            )
+            .add_systems(OnExit(AppState::Loading), break_down_loading_screen);
+    }
+}
+
+/// Asset manifest of the theme in use, mapping the keys of [`AudioAssets`] and [`TextureAssets`] to paths
+#[derive(Resource)]
+pub struct ThemeManifest(pub String);
+
+/// Manifest path of a theme, relative to the assets folder. Unknown themes fall back to the default.
+pub fn theme_manifest(theme: &str) -> String {
+    let theme = if available_themes().iter().any(|known| known == theme) {
+        theme
+    } else {
+        warn!("Unknown theme {}, using the default theme", theme);
+        DEFAULT_THEME
+    };
+    format!("themes/{}{}", theme, MANIFEST_EXTENSION)
+}
+
+/// Themes installed in `assets/themes`, each one a `<name>.assets.ron` manifest
+#[cfg(not(target_arch = "wasm32"))]
+pub fn available_themes() -> Vec<String> {
+    let mut themes: Vec<String> = std::fs::read_dir(THEMES_DIRECTORY)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name().into_string().ok()?;
+            file_name
+                .strip_suffix(MANIFEST_EXTENSION)
+                .map(|theme| theme.to_string())
+        })
+        .collect();
+    if !themes.iter().any(|theme| theme == DEFAULT_THEME) {
+        themes.push(DEFAULT_THEME.to_string());
+    }
+    themes.sort();
+    themes
+}
+
+/// The browser cannot list the assets folder, so only the default theme is available
+#[cfg(target_arch = "wasm32")]
+pub fn available_themes() -> Vec<String> {
+    vec![DEFAULT_THEME.to_string()]
+}
+
+/// Handles of all asset collections, to show how far loading got and what went wrong
+#[derive(Resource)]
+pub struct LoadingProgress {
+    /// The manifest has to be loaded before the themed assets are known. It counts as the first item.
+    manifest: Handle<StandardDynamicAssetCollection>,
+    manifest_read: bool,
+    handles: Vec<HandleUntyped>,
+    pub loaded: usize,
+    /// Path of the first asset that failed to load
+    pub failed: Option<String>,
+}
+
+impl LoadingProgress {
+    pub fn total(&self) -> usize {
+        self.handles.len() + 1
+    }
+
+    pub fn done(&self) -> usize {
+        self.loaded + usize::from(self.manifest_read)
+    }
+
+    pub fn fraction(&self) -> f32 {
+        self.done() as f32 / self.total() as f32
     }
 }
 
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingBar;
+
+#[derive(Component)]
+struct LoadingText;
+
 #[derive(AssetCollection, Resource)]
 pub struct FontAssets {
     #[asset(path = "fonts/FiraSans-Bold.ttf")]
@@ -25,29 +131,47 @@ pub struct FontAssets {
 
 #[derive(AssetCollection, Resource)]
 pub struct AudioAssets {
-    #[asset(path = "sounds/background.ogg")]
+    #[asset(key = "sounds.background")]
     pub background: Handle<AudioSource>,
-    #[asset(path = "sounds/shot.ogg")]
+    #[asset(key = "sounds.music_combat")]
+    pub music_combat: Handle<AudioSource>,
+    #[asset(key = "sounds.music_danger")]
+    pub music_danger: Handle<AudioSource>,
+    #[asset(key = "sounds.tower_shots")]
     pub tower_shots: Handle<AudioSource>,
-    #[asset(path = "sounds/enemybreach.ogg")]
+    #[asset(key = "sounds.enemy_breach")]
     pub enemy_breach: Handle<AudioSource>,
+    #[asset(key = "sounds.enemy_defeated")]
+    pub enemy_defeated: Handle<AudioSource>,
+    #[asset(key = "sounds.wave_start")]
+    pub wave_start: Handle<AudioSource>,
+    #[asset(key = "sounds.tower_built")]
+    pub tower_built: Handle<AudioSource>,
+    #[asset(key = "sounds.piece_picked_up")]
+    pub piece_picked_up: Handle<AudioSource>,
+    #[asset(key = "sounds.piece_placed")]
+    pub piece_placed: Handle<AudioSource>,
+    #[asset(key = "sounds.wrong_slot")]
+    pub wrong_slot: Handle<AudioSource>,
+    #[asset(key = "sounds.puzzle_completed")]
+    pub puzzle_completed: Handle<AudioSource>,
 }
 
 #[derive(AssetCollection, Resource)]
 pub struct TextureAssets {
-    #[asset(path = "textures/blank.png")]
+    #[asset(key = "textures.blank")]
     pub blank: Handle<Image>,
-    #[asset(path = "textures/towerplot.png")]
+    #[asset(key = "textures.tower_plot")]
     pub tower_plot: Handle<Image>,
-    #[asset(path = "textures/tower.png")]
+    #[asset(key = "textures.tower")]
     pub tower: Handle<Image>,
-    #[asset(path = "textures/path.png")]
+    #[asset(key = "textures.path")]
     pub path: Handle<Image>,
-    #[asset(path = "textures/castle.png")]
+    #[asset(key = "textures.castle")]
     pub castle: Handle<Image>,
-    #[asset(path = "textures/cloud.png")]
+    #[asset(key = "textures.cloud")]
     pub cloud: Handle<Image>,
-    #[asset(path = "textures/spawn.png")]
+    #[asset(key = "textures.spawn")]
     pub spawn: Handle<Image>,
 }
 
@@ -64,3 +188,158 @@ impl TextureAssets {
         }
     }
 }
+
+/// The loading state loads the same handles, the asset server only loads each file once.
+/// Themed assets are looked up by key, so their handles are only known once the manifest is read.
+fn track_assets(world: &mut World) {
+    let handles = FontAssets::load(world);
+    let manifest = world.resource::<ThemeManifest>().0.clone();
+    let manifest = world.resource::<AssetServer>().load(manifest);
+    world.insert_resource(LoadingProgress {
+        manifest,
+        manifest_read: false,
+        handles,
+        loaded: 0,
+        failed: None,
+    });
+}
+
+fn update_loading_progress(
+    asset_server: Res<AssetServer>,
+    manifests: Res<Assets<StandardDynamicAssetCollection>>,
+    mut progress: ResMut<LoadingProgress>,
+) {
+    if progress.failed.is_some() {
+        return;
+    }
+    if !progress.manifest_read {
+        if asset_server.get_load_state(&progress.manifest) == LoadState::Failed {
+            let path = asset_path(&asset_server, &progress.manifest.clone_untyped());
+            error!("Failed to load the theme manifest {}", path);
+            progress.failed = Some(path);
+            return;
+        }
+        let Some(manifest) = manifests.get(&progress.manifest) else {
+            return;
+        };
+        let handles: Vec<HandleUntyped> = manifest
+            .0
+            .values()
+            .flat_map(|asset| asset.load(&asset_server))
+            .collect();
+        progress.handles.extend(handles);
+        progress.manifest_read = true;
+    }
+    let mut loaded = 0;
+    for handle in progress.handles.iter() {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => loaded += 1,
+            LoadState::Failed => {
+                let path = asset_path(&asset_server, handle);
+                error!("Failed to load {}", path);
+                progress.failed = Some(path);
+                return;
+            }
+            _ => {}
+        }
+    }
+    if progress.loaded != loaded {
+        progress.loaded = loaded;
+    }
+}
+
+fn asset_path(asset_server: &AssetServer, handle: &HandleUntyped) -> String {
+    asset_server
+        .get_handle_path(handle)
+        .map(|path| path.path().display().to_string())
+        .unwrap_or_else(|| "unknown asset".to_string())
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), LoadingScreen));
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(LoadingScreen)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(300.),
+                        height: Val::Px(20.),
+                        margin: UiRect::all(Val::Px(10.)),
+                        ..Default::default()
+                    },
+                    background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.),
+                                height: Val::Percent(100.),
+                                ..Default::default()
+                            },
+                            background_color: Color::rgb(0.9, 0.9, 0.9).into(),
+                            ..Default::default()
+                        })
+                        .insert(LoadingBar);
+                });
+            // the font is still loading, so this uses bevy's default font
+            parent
+                .spawn(TextBundle::from_section(
+                    "Loading...",
+                    TextStyle {
+                        font_size: 24.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                        ..Default::default()
+                    },
+                ))
+                .insert(LoadingText);
+        });
+}
+
+fn show_loading_progress(
+    progress: Res<LoadingProgress>,
+    mut bar_query: Query<(&mut Style, &mut BackgroundColor), With<LoadingBar>>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+) {
+    if !progress.is_changed() {
+        return;
+    }
+    for (mut style, mut color) in bar_query.iter_mut() {
+        style.width = Val::Percent(progress.fraction() * 100.);
+        if progress.failed.is_some() {
+            *color = Color::rgb(0.8, 0.2, 0.2).into();
+        }
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match progress.failed.as_ref() {
+            Some(path) => format!(
+                "Failed to load \"{}\". Is it missing from the assets folder?",
+                path
+            ),
+            None => format!("Loading... {}/{}", progress.done(), progress.total()),
+        };
+    }
+}
+
+fn break_down_loading_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<LoadingScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<LoadingProgress>();
+}
@@ -3,20 +3,24 @@
 
 use bevy::prelude::*;
 use bevy::window::WindowResolution;
+use oicana::settings::Settings;
 use oicana::GamePlugin;
 
 #[bevy_main]
 fn main() {
+    let settings = Settings::load();
     App::new()
-        .insert_resource(Msaa::Sample4)
+        .insert_resource(settings.msaa())
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
-                resolution: WindowResolution::new(800., 600.),
+                resolution: WindowResolution::new(settings.resolution.0, settings.resolution.1),
+                mode: settings.window_mode.into(),
                 title: "Oicana".to_string(),
                 ..default()
             }),
             ..default()
         }))
+        .insert_resource(settings)
         .add_plugins(GamePlugin)
         .run();
 }
@@ -10,7 +10,9 @@ impl Plugin for MapPlugin {
         let map = Map::load_map();
         app.insert_resource(map.gather_trees())
             .insert_resource(map)
-            .add_systems(OnEnter(AppState::Menu), (render_map, setup_camera));
+            .add_event::<MapChanged>()
+            .add_systems(OnEnter(AppState::Menu), (render_map, setup_camera))
+            .add_systems(Update, refresh_map.run_if(in_state(AppState::Menu)));
     }
 }
 
@@ -25,6 +27,74 @@ pub enum Tile {
     Empty,
 }
 
+impl Tile {
+    /// Character of the tile in the map text format
+    pub fn to_char(&self) -> char {
+        match self {
+            Tile::Path => '+',
+            Tile::Spawn => 'a',
+            Tile::TowerPlot => '.',
+            Tile::Tower => '0',
+            Tile::Castle => 'q',
+            Tile::Cloud => 't',
+            Tile::Empty => '#',
+        }
+    }
+}
+
+/// Why a map cannot be played. Rows and columns count from 1 at the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapError {
+    Empty,
+    RaggedRow {
+        row: usize,
+    },
+    UnknownTile {
+        tile: char,
+        row: usize,
+        column: usize,
+    },
+    /// There has to be exactly one spawn
+    Spawns(usize),
+    /// There has to be exactly one castle
+    Castles(usize),
+    /// The path does not lead from the spawn to the castle
+    PathNotConnected,
+    /// Path tile that is not part of the way to the castle
+    UnusedPath {
+        row: usize,
+        column: usize,
+    },
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "the map is empty"),
+            MapError::RaggedRow { row } => {
+                write!(f, "row {} is not as long as the first row", row)
+            }
+            MapError::UnknownTile { tile, row, column } => {
+                write!(
+                    f,
+                    "unknown tile '{}' in row {}, column {}",
+                    tile, row, column
+                )
+            }
+            MapError::Spawns(count) => write!(f, "expected one spawn, found {}", count),
+            MapError::Castles(count) => write!(f, "expected one castle, found {}", count),
+            MapError::PathNotConnected => {
+                write!(f, "the path does not lead from the spawn to the castle")
+            }
+            MapError::UnusedPath { row, column } => write!(
+                f,
+                "the path tile in row {}, column {} is not on the way to the castle",
+                row, column
+            ),
+        }
+    }
+}
+
 #[derive(Default)]
 struct Point {
     pub x: usize,
@@ -43,7 +113,7 @@ impl Coordinate {
     }
 }
 
-#[derive(Debug, Resource)]
+#[derive(Clone, Debug, Resource)]
 pub struct Map {
     pub height: usize,
     pub width: usize,
@@ -54,10 +124,12 @@ pub struct Map {
     pub waypoints: Vec<Coordinate>,
 }
 
+/// The [`Map`] resource was replaced and has to be rendered again
+#[derive(Event)]
+pub struct MapChanged;
+
 #[derive(Component)]
 pub struct MapTile {
-    pub column: usize,
-    pub row: usize,
     pub tile: Tile,
 }
 
@@ -76,6 +148,11 @@ impl Map {
             ##t#.#.####t#\n\
             #############";
 
+        Map::parse(MAP_STR).expect("the built-in map is valid")
+    }
+
+    /// Build a map from rows of tile characters, top row first
+    pub fn parse(map_str: &str) -> Result<Self, MapError> {
         let mut map = Map {
             height: 0,
             width: 0,
@@ -87,11 +164,20 @@ impl Map {
         };
 
         let mut preliminary_waypoints = vec![];
-        let mut spawn: Point = Default::default();
-        let mut sink: Point = Default::default();
-        map.height = MAP_STR.lines().count();
-        for (row_index, line) in MAP_STR.lines().enumerate() {
-            let row_index = map.height - row_index - 1;
+        let mut spawns = vec![];
+        let mut sinks = vec![];
+        map.height = map_str.lines().count();
+        if map.height == 0 {
+            return Err(MapError::Empty);
+        }
+        let width = map_str.lines().next().unwrap().chars().count();
+        for (line_index, line) in map_str.lines().enumerate() {
+            if line.chars().count() != width {
+                return Err(MapError::RaggedRow {
+                    row: line_index + 1,
+                });
+            }
+            let row_index = map.height - line_index - 1;
             let mut row = vec![];
             for (column_index, char) in line.chars().enumerate() {
                 match char {
@@ -107,10 +193,10 @@ impl Map {
                         row.push(Tile::Path)
                     }
                     'a' => {
-                        spawn = Point {
+                        spawns.push(Point {
                             x: column_index,
                             y: row_index,
-                        };
+                        });
                         map.spawn = Coordinate {
                             x: column_index as f32 * map.tile_size,
                             y: row_index as f32 * map.tile_size,
@@ -118,30 +204,53 @@ impl Map {
                         row.push(Tile::Spawn)
                     }
                     'q' => {
-                        sink = Point {
+                        sinks.push(Point {
                             x: column_index,
                             y: row_index,
-                        };
+                        });
                         map.sink = Coordinate {
                             x: column_index as f32 * map.tile_size,
                             y: row_index as f32 * map.tile_size,
                         };
                         row.push(Tile::Castle)
                     }
-                    _ => panic!("unknown map char {}", char),
+                    _ => {
+                        return Err(MapError::UnknownTile {
+                            tile: char,
+                            row: line_index + 1,
+                            column: column_index + 1,
+                        })
+                    }
                 }
             }
             map.tiles.push(row);
         }
         // otherwise my map is head down O.o
         map.tiles.reverse();
-        map.width = map.tiles.first().unwrap().len();
-        map.create_way_points(preliminary_waypoints, spawn, sink);
+        map.width = width;
+        if spawns.len() != 1 {
+            return Err(MapError::Spawns(spawns.len()));
+        }
+        if sinks.len() != 1 {
+            return Err(MapError::Castles(sinks.len()));
+        }
+        map.create_way_points(preliminary_waypoints, spawns.remove(0), sinks.remove(0))?;
+
+        Ok(map)
+    }
 
-        map
+    /// The map in the text format [`Map::parse`] reads
+    pub fn to_map_string(&self) -> String {
+        tiles_to_string(&self.tiles)
     }
 
-    fn create_way_points(&mut self, mut waypoints: Vec<Point>, spawn: Point, sink: Point) {
+    /// Follow the path from the spawn to the castle. Every path tile has to be on the way.
+    fn create_way_points(
+        &mut self,
+        mut waypoints: Vec<Point>,
+        spawn: Point,
+        sink: Point,
+    ) -> Result<(), MapError> {
         let mut last_point = spawn;
         loop {
             let next_point_position = waypoints.iter().position(|point| {
@@ -153,11 +262,20 @@ impl Map {
                 length > 0.9 && length < 1.1
             });
             if next_point_position.is_none() {
+                if last_point.x.abs_diff(sink.x) + last_point.y.abs_diff(sink.y) != 1 {
+                    return Err(MapError::PathNotConnected);
+                }
+                if let Some(unused) = waypoints.first() {
+                    return Err(MapError::UnusedPath {
+                        row: self.height - unused.y,
+                        column: unused.x + 1,
+                    });
+                }
                 self.waypoints.push(Coordinate {
                     x: sink.x as f32 * self.tile_size,
                     y: sink.y as f32 * self.tile_size,
                 });
-                return;
+                return Ok(());
             }
             let next_point_position = next_point_position.unwrap();
             let next_point = waypoints.remove(next_point_position);
@@ -169,33 +287,130 @@ impl Map {
         }
     }
 
-    fn gather_trees(&self) -> Trees {
-        let mut tree_positions: Vec<Coordinate> = vec![];
-        for (row_index, row) in self.tiles.iter().enumerate() {
+    /// Coordinates of all plots that can hold a tower, from the top left to the bottom right
+    pub fn plot_coordinates(&self) -> Vec<Coordinate> {
+        let mut plots = vec![];
+        for (row_index, row) in self.tiles.iter().enumerate().rev() {
             for (column_index, tile) in row.iter().enumerate() {
-                if tile == &Tile::Cloud {
-                    tree_positions.push(Coordinate {
+                if tile == &Tile::TowerPlot || tile == &Tile::Tower {
+                    plots.push(Coordinate {
                         x: column_index as f32 * self.tile_size,
                         y: row_index as f32 * self.tile_size,
-                    })
+                    });
+                }
+            }
+        }
+        plots
+    }
+
+    pub(crate) fn gather_trees(&self) -> Trees {
+        let mut tree_positions: Vec<Vec2> = vec![];
+        for (row_index, row) in self.tiles.iter().enumerate() {
+            for (column_index, tile) in row.iter().enumerate() {
+                if tile == &Tile::Cloud {
+                    tree_positions
+                        .push(Vec2::new(column_index as f32, row_index as f32) * self.tile_size);
                 }
             }
         }
+        if tree_positions.is_empty() {
+            return Trees {
+                tile_size: self.tile_size,
+                closest: vec![],
+            };
+        }
 
+        let closest = (0..self.height)
+            .map(|row_index| {
+                (0..self.width)
+                    .map(|column_index| {
+                        let center =
+                            Vec2::new(column_index as f32, row_index as f32) * self.tile_size;
+                        *tree_positions
+                            .iter()
+                            .min_by(|a, b| {
+                                a.distance_squared(center)
+                                    .total_cmp(&b.distance_squared(center))
+                            })
+                            .unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
         Trees {
-            coordinates: tree_positions,
+            tile_size: self.tile_size,
+            closest,
         }
     }
 }
 
-fn setup_camera(mut commands: Commands, map: Res<Map>) {
+/// Rows of tiles, bottom row first like [`Map::tiles`], in the text format with the top row first
+pub fn tiles_to_string(tiles: &[Vec<Tile>]) -> String {
+    tiles
+        .iter()
+        .rev()
+        .map(|row| row.iter().map(Tile::to_char).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub(crate) fn camera_position(map: &Map) -> Vec2 {
+    Vec2::new(map.width as f32 / 2. - 0.5, map.height as f32 / 2. - 0.5) * map.tile_size
+}
+
+/// The menu is entered again coming back from the editor, so reuse the camera if there is one
+fn setup_camera(
+    mut commands: Commands,
+    map: Res<Map>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    let position = camera_position(&map);
+    if let Ok(mut transform) = camera_query.get_single_mut() {
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+        return;
+    }
     let mut camera_bundle = Camera2dBundle::default();
-    camera_bundle.transform.translation.x = (map.width as f32 / 2. - 0.5) * map.tile_size;
-    camera_bundle.transform.translation.y = (map.height as f32 / 2. - 0.5) * map.tile_size;
+    camera_bundle.transform.translation.x = position.x;
+    camera_bundle.transform.translation.y = position.y;
     commands.spawn(camera_bundle);
 }
 
-fn render_map(mut commands: Commands, map: Res<Map>, texture_assets: Res<TextureAssets>) {
+fn render_map(
+    mut commands: Commands,
+    map: Res<Map>,
+    texture_assets: Res<TextureAssets>,
+    tile_query: Query<Entity, With<MapTile>>,
+) {
+    for entity in tile_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_map_tiles(&mut commands, &map, &texture_assets);
+}
+
+fn refresh_map(
+    mut commands: Commands,
+    map: Res<Map>,
+    texture_assets: Res<TextureAssets>,
+    mut map_changed: EventReader<MapChanged>,
+    tile_query: Query<Entity, With<MapTile>>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    if map_changed.iter().last().is_none() {
+        return;
+    }
+    for entity in tile_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_map_tiles(&mut commands, &map, &texture_assets);
+    for mut transform in camera_query.iter_mut() {
+        let position = camera_position(&map);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+pub(crate) fn spawn_map_tiles(commands: &mut Commands, map: &Map, texture_assets: &TextureAssets) {
     for row in 0..map.height {
         for column in 0..map.width {
             let tile = &map.tiles[row][column];
@@ -209,11 +424,70 @@ fn render_map(mut commands: Commands, map: Res<Map>, texture_assets: Res<Texture
                     )),
                     ..Default::default()
                 })
-                .insert(MapTile {
-                    column,
-                    row,
-                    tile: tile.clone(),
-                });
+                .insert(MapTile { tile: tile.clone() });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_map_is_valid() {
+        let map = Map::load_map();
+        assert_eq!(map.width, 13);
+        assert_eq!(map.waypoints.last(), Some(&map.sink));
+    }
+
+    #[test]
+    fn tameables_head_for_the_closest_tree() {
+        let map = Map::parse("t...\na++q\n...t").unwrap();
+        let trees = map.gather_trees();
+        let size = map.tile_size;
+        // rows are stored bottom first, so the tree in the first text row is at the top
+        assert_eq!(
+            trees.closest(Vec2::new(0.2, 1.8) * size),
+            Vec2::new(0., 2.) * size
+        );
+        assert_eq!(
+            trees.closest(Vec2::new(2.6, 0.4) * size),
+            Vec2::new(3., 0.) * size
+        );
+        assert_eq!(trees.closest(Vec2::splat(-50.)), Vec2::new(0., 2.) * size);
+        let no_trees = Map::parse("a++q").unwrap().gather_trees();
+        assert_eq!(no_trees.closest(Vec2::ONE * size), Vec2::ZERO);
+    }
+
+    #[test]
+    fn map_text_round_trips() {
+        let map = Map::load_map();
+        let reparsed = Map::parse(&map.to_map_string()).unwrap();
+        assert_eq!(reparsed.tiles, map.tiles);
+        assert_eq!(reparsed.waypoints, map.waypoints);
+    }
+
+    #[test]
+    fn invalid_maps_are_rejected() {
+        assert_eq!(Map::parse("").unwrap_err(), MapError::Empty);
+        assert_eq!(
+            Map::parse("a+q\n#").unwrap_err(),
+            MapError::RaggedRow { row: 2 }
+        );
+        assert_eq!(
+            Map::parse("a+x").unwrap_err(),
+            MapError::UnknownTile {
+                tile: 'x',
+                row: 1,
+                column: 3
+            }
+        );
+        assert_eq!(Map::parse("#+q").unwrap_err(), MapError::Spawns(0));
+        assert_eq!(Map::parse("a+q\na+q").unwrap_err(), MapError::Spawns(2));
+        assert_eq!(Map::parse("a+#q").unwrap_err(), MapError::PathNotConnected);
+        assert_eq!(
+            Map::parse("a+q\n###\n+##").unwrap_err(),
+            MapError::UnusedPath { row: 3, column: 1 }
+        );
+    }
+}
@@ -1,5 +1,8 @@
+use crate::bot::{Bot, GreedyStrategy};
 use crate::loading::FontAssets;
-use crate::ui::ButtonColors;
+use crate::mods::{spawn_mods_screen, Mods};
+use crate::settings::{spawn_settings_screen, Settings};
+use crate::ui::{ButtonColors, GameState};
 use crate::AppState;
 use bevy::prelude::*;
 
@@ -8,24 +11,151 @@ pub struct MenuPlugin;
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(AppState::Menu), setup_menu)
-            .add_systems(Update, click_play_button.run_if(in_state(AppState::Menu)));
+            .add_systems(
+                OnEnter(AppState::InGame),
+                start_demo.run_if(resource_exists::<AutoplayDemo>()),
+            )
+            .add_systems(
+                Update,
+                click_menu_buttons
+                    .run_if(in_state(AppState::Menu).or_else(resource_exists::<AutoplayDemo>())),
+            )
+            .add_systems(
+                Update,
+                restart_demo
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(resource_exists::<AutoplayDemo>()),
+            )
+            .add_systems(OnExit(AppState::InGame), break_down_menu);
     }
 }
 
+#[derive(Component)]
+struct Menu;
+
 #[derive(Component)]
 struct PlayButton;
 
+/// Starts a game played by a bot behind the menu
+#[derive(Component)]
+struct AutoplayButton;
+
+#[derive(Component)]
+struct StopDemoButton;
+
+/// A bot plays the game while the menu is shown on top of it
+#[derive(Resource)]
+struct AutoplayDemo;
+
+#[derive(Component)]
+struct SettingsButton;
+
+#[derive(Component)]
+struct ModsButton;
+
+#[derive(Component)]
+struct EditorButton;
+
 fn setup_menu(
     mut commands: Commands,
     font_assets: Res<FontAssets>,
     button_colors: Res<ButtonColors>,
+) {
+    spawn_main_menu(&mut commands, &font_assets, &button_colors);
+}
+
+fn menu_root() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub(crate) fn spawn_main_menu(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
 ) {
     commands
+        .spawn(menu_root())
+        .insert(Menu)
+        .with_children(|parent| {
+            spawn_button(parent, "Play", PlayButton, font_assets, button_colors);
+            spawn_button(
+                parent,
+                "Autoplay",
+                AutoplayButton,
+                font_assets,
+                button_colors,
+            );
+            spawn_button(
+                parent,
+                "Settings",
+                SettingsButton,
+                font_assets,
+                button_colors,
+            );
+            spawn_button(parent, "Mods", ModsButton, font_assets, button_colors);
+            spawn_button(parent, "Editor", EditorButton, font_assets, button_colors);
+        });
+}
+
+/// Screens that leave the game are only reachable after stopping the demo
+fn start_demo(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+) {
+    commands.insert_resource(Bot::new(GreedyStrategy));
+    commands
+        .spawn(menu_root())
+        .insert(Menu)
+        .with_children(|parent| {
+            spawn_button(parent, "Play", PlayButton, &font_assets, &button_colors);
+            spawn_button(
+                parent,
+                "Stop demo",
+                StopDemoButton,
+                &font_assets,
+                &button_colors,
+            );
+        });
+}
+
+/// The demo starts over once the bot lost
+fn restart_demo(mut state: ResMut<NextState<AppState>>, mut game_state: ResMut<GameState>) {
+    if game_state.health < 1 {
+        *game_state = GameState::default();
+        state.set(AppState::Restart);
+    }
+}
+
+fn break_down_menu(mut commands: Commands, menu_query: Query<Entity, With<Menu>>) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    label: &str,
+    marker: impl Component,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+) {
+    parent
         .spawn(ButtonBundle {
             style: Style {
-                width: Val::Px(120.0),
+                width: Val::Px(180.0),
                 height: Val::Px(50.0),
-                margin: UiRect::all(Val::Auto),
+                margin: UiRect::all(Val::Px(10.)),
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 ..Default::default()
@@ -33,12 +163,12 @@ fn setup_menu(
             background_color: button_colors.normal.into(),
             ..Default::default()
         })
-        .insert(PlayButton)
+        .insert(marker)
         .with_children(|parent| {
             parent.spawn(TextBundle {
                 text: Text {
                     sections: vec![TextSection {
-                        value: "Play".to_string(),
+                        value: label.to_string(),
                         style: TextStyle {
                             font: font_assets.fira_sans.clone(),
                             font_size: 40.0,
@@ -52,22 +182,91 @@ fn setup_menu(
         });
 }
 
-fn click_play_button(
+/// A clicked menu button and which one it is
+type MenuButton = (
+    &'static Interaction,
+    &'static mut BackgroundColor,
+    Option<&'static AutoplayButton>,
+    Option<&'static SettingsButton>,
+    Option<&'static ModsButton>,
+    Option<&'static EditorButton>,
+    Option<&'static StopDemoButton>,
+);
+
+type MenuButtonFilter = (
+    Changed<Interaction>,
+    Or<(
+        With<PlayButton>,
+        With<AutoplayButton>,
+        With<SettingsButton>,
+        With<ModsButton>,
+        With<EditorButton>,
+        With<StopDemoButton>,
+    )>,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn click_menu_buttons(
     mut commands: Commands,
     button_colors: Res<ButtonColors>,
     mut state: ResMut<NextState<AppState>>,
-    mut interaction_query: Query<
-        (Entity, &Interaction, &mut BackgroundColor, &Children),
-        With<Button>,
-    >,
-    text_query: Query<Entity, With<Text>>,
+    mut game_state: ResMut<GameState>,
+    demo: Option<Res<AutoplayDemo>>,
+    mut interaction_query: Query<MenuButton, MenuButtonFilter>,
+    menu_query: Query<Entity, With<Menu>>,
+    font_assets: Res<FontAssets>,
+    settings: Res<Settings>,
+    mods: Res<Mods>,
 ) {
-    for (button, interaction, mut color, children) in interaction_query.iter_mut() {
-        let text = text_query.get(children[0]).unwrap();
+    for (
+        interaction,
+        mut color,
+        autoplay,
+        settings_button,
+        mods_button,
+        editor_button,
+        stop_demo,
+    ) in interaction_query.iter_mut()
+    {
         match *interaction {
             Interaction::Pressed => {
-                commands.entity(button).despawn();
-                commands.entity(text).despawn();
+                for menu in menu_query.iter() {
+                    commands.entity(menu).despawn_recursive();
+                }
+                if settings_button.is_some() {
+                    spawn_settings_screen(&mut commands, &font_assets, &button_colors, &settings);
+                    return;
+                }
+                if mods_button.is_some() {
+                    spawn_mods_screen(
+                        &mut commands,
+                        &font_assets,
+                        &button_colors,
+                        &mods,
+                        &settings,
+                    );
+                    return;
+                }
+                if editor_button.is_some() {
+                    state.set(AppState::Editor);
+                    return;
+                }
+                if autoplay.is_some() {
+                    commands.insert_resource(AutoplayDemo);
+                    state.set(AppState::InGame);
+                    return;
+                }
+                if demo.is_some() {
+                    // the demo game is thrown away, both for playing and for going back to the menu
+                    commands.remove_resource::<AutoplayDemo>();
+                    *game_state = GameState::default();
+                    if stop_demo.is_some() {
+                        state.set(AppState::Menu);
+                    } else {
+                        state.set(AppState::Restart);
+                    }
+                    return;
+                }
                 state.set(AppState::InGame);
             }
             Interaction::Hovered => {
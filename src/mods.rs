@@ -0,0 +1,675 @@
+use crate::enemies::EnemyForm;
+use crate::forms::{EnemyDefinitions, FormTraits};
+use crate::loading::FontAssets;
+use crate::map::{Map, MapChanged};
+use crate::menu::spawn_main_menu;
+use crate::settings::Settings;
+use crate::towers::{TowerDefinition, HEAVY_SPEED_PENALTY};
+use crate::ui::ButtonColors;
+use crate::waves::Waves;
+use crate::AppState;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+#[cfg(not(target_arch = "wasm32"))]
+const MODS_DIRECTORY: &str = "mods";
+const MANIFEST_FILE: &str = "mod.ron";
+
+/// Finds mods in the `mods` folder and merges the enabled ones into the game's content
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        let enabled = app
+            .world
+            .get_resource::<Settings>()
+            .map(|settings| settings.enabled_mods.clone())
+            .unwrap_or_else(|| Settings::load().enabled_mods);
+        let mut mods = Mods::discover();
+        mods.merge(&enabled).insert_into(&mut app.world);
+        app.insert_resource(mods)
+            .add_systems(Update, click_mods.run_if(in_state(AppState::Menu)));
+    }
+}
+
+/// Describes a mod. Lives in `mods/<folder>/mod.ron`, content files are relative to that folder.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModManifest {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// Map in the same format as the built-in one
+    #[serde(default)]
+    pub map: Option<String>,
+    /// List of waves, replacing the built-in waves
+    #[serde(default)]
+    pub waves: Option<String>,
+    /// A [`TowerDefinition`]
+    #[serde(default)]
+    pub towers: Option<String>,
+    /// [`FormTraits`] by enemy form
+    #[serde(default)]
+    pub enemies: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct ModContent {
+    pub map: Option<Map>,
+    pub waves: Option<Waves>,
+    pub towers: Option<TowerDefinition>,
+    pub enemies: HashMap<EnemyForm, FormTraits>,
+}
+
+pub struct ModPackage {
+    /// Folder of the mod inside the mods folder
+    pub folder: String,
+    pub manifest: Option<ModManifest>,
+    pub content: ModContent,
+    /// Mods with errors cannot be enabled
+    pub errors: Vec<String>,
+}
+
+impl ModPackage {
+    /// Read and validate a mod. `read` returns the content of a file in the mod's folder.
+    pub fn load(folder: &str, read: impl Fn(&str) -> Option<String>) -> Self {
+        let mut package = ModPackage {
+            folder: folder.to_string(),
+            manifest: None,
+            content: ModContent::default(),
+            errors: vec![],
+        };
+        let Some(manifest) = package.parse::<ModManifest>(&read, MANIFEST_FILE) else {
+            return package;
+        };
+        if manifest.id.trim().is_empty() {
+            package.errors.push(format!("{} has no id", MANIFEST_FILE));
+        }
+        if let Some(file) = manifest.map.as_ref() {
+            if let Some(map_str) = package.read(&read, file) {
+                match Map::parse(map_str.trim_end()) {
+                    Ok(map) => package.content.map = Some(map),
+                    Err(error) => package.errors.push(format!("{}: {}", file, error)),
+                }
+            }
+        }
+        if let Some(file) = manifest.waves.as_ref() {
+            package.content.waves = package.parse::<Waves>(&read, file);
+            package.validate_waves(file);
+        }
+        if let Some(file) = manifest.towers.as_ref() {
+            package.content.towers = package.parse::<TowerDefinition>(&read, file);
+            package.validate_towers(file);
+        }
+        if let Some(file) = manifest.enemies.as_ref() {
+            package.content.enemies = package
+                .parse::<HashMap<EnemyForm, FormTraits>>(&read, file)
+                .unwrap_or_default();
+            package.validate_enemies(file);
+        }
+        package.manifest = Some(manifest);
+        package
+    }
+
+    pub fn id(&self) -> &str {
+        self.manifest
+            .as_ref()
+            .map_or(&self.folder, |manifest| &manifest.id)
+    }
+
+    pub fn name(&self) -> &str {
+        self.manifest
+            .as_ref()
+            .map_or(&self.folder, |manifest| &manifest.name)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn read(&mut self, read: &impl Fn(&str) -> Option<String>, file: &str) -> Option<String> {
+        let content = read(file);
+        if content.is_none() {
+            self.errors.push(format!("{} is missing", file));
+        }
+        content
+    }
+
+    fn parse<T: DeserializeOwned>(
+        &mut self,
+        read: &impl Fn(&str) -> Option<String>,
+        file: &str,
+    ) -> Option<T> {
+        let content = self.read(read, file)?;
+        ron::from_str(&content)
+            .map_err(|error| self.errors.push(format!("{}: {}", file, error)))
+            .ok()
+    }
+
+    fn validate_waves(&mut self, file: &str) {
+        let Some(waves) = self.content.waves.as_ref() else {
+            return;
+        };
+        if waves.waves.is_empty() {
+            self.errors.push(format!("{} has no waves", file));
+        }
+        for (index, wave) in waves.waves.iter().enumerate() {
+            if wave
+                .abilities
+                .iter()
+                .any(|ability| !(0. ..=1.).contains(&ability.chance))
+            {
+                self.errors.push(format!(
+                    "{}: ability chances of wave {} have to be between 0 and 1",
+                    file,
+                    index + 1
+                ));
+            }
+            let Some(boss) = wave.boss.as_ref() else {
+                continue;
+            };
+            if boss.health_multiplier < 1
+                || boss
+                    .phases
+                    .iter()
+                    .any(|phase| !(0. ..1.).contains(&phase.health_threshold))
+            {
+                self.errors.push(format!(
+                    "{}: the boss of wave {} needs a positive health multiplier and phase thresholds between 0 and 1",
+                    file,
+                    index + 1
+                ));
+            }
+        }
+    }
+
+    fn validate_towers(&mut self, file: &str) {
+        let Some(towers) = self.content.towers.as_ref() else {
+            return;
+        };
+        if towers.range <= 0. || towers.damage < 0 {
+            self.errors.push(format!(
+                "{}: towers need a positive range and must not heal",
+                file
+            ));
+        }
+        // heavy towers shoot slower bullets, they still have to move
+        if towers.speed <= HEAVY_SPEED_PENALTY {
+            self.errors.push(format!(
+                "{}: tower speed has to be above {}",
+                file, HEAVY_SPEED_PENALTY
+            ));
+        }
+        if towers.upgrade_range < 0. || towers.upgrade_speed < 0. || towers.upgrade_damage < 0 {
+            self.errors
+                .push(format!("{}: upgrades must not weaken towers", file));
+        }
+    }
+
+    fn validate_enemies(&mut self, file: &str) {
+        for (form, traits) in self.content.enemies.iter() {
+            if traits.speed <= 0. || traits.health <= 0. || traits.armor < 0 {
+                self.errors.push(format!(
+                    "{}: {:?} enemies need a positive speed and health and no negative armor",
+                    file, form
+                ));
+            }
+        }
+    }
+}
+
+/// All mods found in the mods folder, in load order
+#[derive(Default, Resource)]
+pub struct Mods {
+    pub packages: Vec<ModPackage>,
+    /// Content changed by more than one enabled mod. The mod first in load order wins.
+    pub conflicts: Vec<String>,
+}
+
+/// Everything a mod can change
+pub struct Registries {
+    pub map: Map,
+    pub waves: Waves,
+    pub towers: TowerDefinition,
+    pub enemies: EnemyDefinitions,
+}
+
+impl Default for Registries {
+    fn default() -> Self {
+        Registries {
+            map: Map::load_map(),
+            waves: Waves::default(),
+            towers: TowerDefinition::default(),
+            enemies: EnemyDefinitions::default(),
+        }
+    }
+}
+
+impl Registries {
+    pub fn insert_into(self, world: &mut World) {
+        world.insert_resource(self.map.gather_trees());
+        world.insert_resource(self.map);
+        world.insert_resource(self.waves);
+        world.insert_resource(self.towers);
+        world.insert_resource(self.enemies);
+    }
+}
+
+impl Mods {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn discover() -> Self {
+        let mut folders: Vec<String> = std::fs::read_dir(MODS_DIRECTORY)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        folders.sort();
+        let packages = folders
+            .iter()
+            .map(|folder| {
+                let directory = std::path::Path::new(MODS_DIRECTORY).join(folder);
+                ModPackage::load(folder, |file| {
+                    std::fs::read_to_string(directory.join(file)).ok()
+                })
+            })
+            .collect();
+        Mods::new(packages)
+    }
+
+    /// The browser cannot list folders, so there are no mods on the web
+    #[cfg(target_arch = "wasm32")]
+    pub fn discover() -> Self {
+        Mods::default()
+    }
+
+    /// Reports mods that reuse the id of an earlier mod
+    pub fn new(mut packages: Vec<ModPackage>) -> Self {
+        let mut ids: HashMap<String, String> = HashMap::default();
+        for package in packages.iter_mut() {
+            if package.manifest.is_none() {
+                continue;
+            }
+            let id = package.id().to_string();
+            match ids.get(&id) {
+                Some(folder) => package
+                    .errors
+                    .push(format!("the id {} is already used by {}", id, folder)),
+                None => {
+                    ids.insert(id, package.folder.clone());
+                }
+            }
+        }
+        for package in packages.iter().filter(|package| !package.is_valid()) {
+            warn!(
+                "Mod in {} is invalid: {}",
+                package.folder,
+                package.errors.join(", ")
+            );
+        }
+        Mods {
+            packages,
+            conflicts: vec![],
+        }
+    }
+
+    /// Put the content of the enabled mods over the built-in content
+    pub fn merge(&mut self, enabled: &[String]) -> Registries {
+        let mut registries = Registries::default();
+        let mut owners: HashMap<String, &str> = HashMap::default();
+        let mut conflicts = vec![];
+        for package in self
+            .packages
+            .iter()
+            .filter(|package| package.is_valid() && enabled.iter().any(|id| id == package.id()))
+        {
+            let mut claim = |content: String| match owners.get(&content) {
+                Some(owner) => {
+                    conflicts.push(format!(
+                        "{} and {} both change {}, using {}",
+                        owner,
+                        package.id(),
+                        content,
+                        owner
+                    ));
+                    false
+                }
+                None => {
+                    owners.insert(content, package.id());
+                    true
+                }
+            };
+            let content = &package.content;
+            if let Some(map) = content.map.as_ref() {
+                if claim("the map".to_string()) {
+                    registries.map = map.clone();
+                }
+            }
+            if let Some(waves) = content.waves.as_ref() {
+                if claim("the waves".to_string()) {
+                    registries.waves = waves.clone();
+                }
+            }
+            if let Some(towers) = content.towers.as_ref() {
+                if claim("the towers".to_string()) {
+                    registries.towers = towers.clone();
+                }
+            }
+            for (form, traits) in content.enemies.iter() {
+                if claim(format!("{:?} enemies", form)) {
+                    registries.enemies.insert(form.clone(), traits.clone());
+                }
+            }
+        }
+        for conflict in conflicts.iter() {
+            warn!("Mod conflict: {}", conflict);
+        }
+        self.conflicts = conflicts;
+        registries
+    }
+}
+
+#[derive(Component)]
+struct ModsScreen;
+
+/// Toggles the mod with this index in [`Mods::packages`]
+#[derive(Component)]
+struct ModToggle(usize);
+
+#[derive(Component)]
+struct ModsBackButton;
+
+fn mod_label(package: &ModPackage, settings: &Settings) -> String {
+    if !package.is_valid() {
+        return format!("{}: invalid", package.name());
+    }
+    let enabled = settings.enabled_mods.iter().any(|id| id == package.id());
+    let version = package
+        .manifest
+        .as_ref()
+        .map_or("", |manifest| manifest.version.as_str());
+    format!(
+        "{} {}: {}",
+        package.name(),
+        version,
+        if enabled { "On" } else { "Off" }
+    )
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    label: String,
+    marker: impl Component,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(400.0),
+                height: Val::Px(40.0),
+                margin: UiRect::all(Val::Px(4.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            background_color: button_colors.normal.into(),
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 26.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
+fn spawn_note(parent: &mut ChildBuilder, note: String, font_assets: &FontAssets) {
+    parent.spawn(TextBundle::from_section(
+        note,
+        TextStyle {
+            font: font_assets.fira_sans.clone(),
+            font_size: 16.0,
+            color: Color::rgb(0.9, 0.5, 0.5),
+        },
+    ));
+}
+
+pub(crate) fn spawn_mods_screen(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    mods: &Mods,
+    settings: &Settings,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(ModsScreen)
+        .with_children(|parent| {
+            if mods.packages.is_empty() {
+                spawn_note(
+                    parent,
+                    "No mods found in the mods folder".to_string(),
+                    font_assets,
+                );
+            }
+            for (index, package) in mods.packages.iter().enumerate() {
+                spawn_button(
+                    parent,
+                    mod_label(package, settings),
+                    ModToggle(index),
+                    font_assets,
+                    button_colors,
+                );
+                for error in package.errors.iter() {
+                    spawn_note(parent, error.clone(), font_assets);
+                }
+            }
+            for conflict in mods.conflicts.iter() {
+                spawn_note(parent, format!("Conflict: {}", conflict), font_assets);
+            }
+            spawn_button(
+                parent,
+                "Back".to_string(),
+                ModsBackButton,
+                font_assets,
+                button_colors,
+            );
+        });
+}
+
+/// A clicked button of the mods screen, either a mod toggle or the way back
+type ModsScreenButton = (
+    &'static Interaction,
+    &'static mut BackgroundColor,
+    Option<&'static ModToggle>,
+    Option<&'static ModsBackButton>,
+);
+
+#[allow(clippy::too_many_arguments)]
+fn click_mods(
+    mut commands: Commands,
+    mut mods: ResMut<Mods>,
+    mut settings: ResMut<Settings>,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    mut map_changed: EventWriter<MapChanged>,
+    mut interaction_query: Query<ModsScreenButton, Changed<Interaction>>,
+    screen_query: Query<Entity, With<ModsScreen>>,
+) {
+    for (interaction, mut color, toggle, back) in interaction_query.iter_mut() {
+        if toggle.is_none() && back.is_none() {
+            continue;
+        }
+        match *interaction {
+            Interaction::Pressed => {
+                for screen in screen_query.iter() {
+                    commands.entity(screen).despawn_recursive();
+                }
+                if back.is_some() {
+                    settings.save();
+                    spawn_main_menu(&mut commands, &font_assets, &button_colors);
+                    return;
+                }
+                let package = &mods.packages[toggle.unwrap().0];
+                if package.is_valid() {
+                    let id = package.id().to_string();
+                    if settings.enabled_mods.contains(&id) {
+                        settings.enabled_mods.retain(|enabled| *enabled != id);
+                    } else {
+                        settings.enabled_mods.push(id);
+                    }
+                    let registries = mods.merge(&settings.enabled_mods);
+                    commands.add(move |world: &mut World| registries.insert_into(world));
+                    map_changed.send(MapChanged);
+                }
+                spawn_mods_screen(
+                    &mut commands,
+                    &font_assets,
+                    &button_colors,
+                    &mods,
+                    &settings,
+                );
+                return;
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_package(folder: &str, files: &[(&str, &str)]) -> ModPackage {
+        let files: HashMap<String, String> = files
+            .iter()
+            .map(|(name, content)| (name.to_string(), content.to_string()))
+            .collect();
+        ModPackage::load(folder, |file| files.get(file).cloned())
+    }
+
+    const WAVES: &str =
+        "[(enemies: 3), (enemies: 5, abilities: [(ability: Sprinter, chance: 0.5)])]";
+
+    #[test]
+    fn valid_mod_is_loaded() {
+        let package = load_package(
+            "winter",
+            &[
+                (
+                    "mod.ron",
+                    r#"(id: "winter", name: "Winter", map: Some("map.txt"), waves: Some("waves.ron"), enemies: Some("enemies.ron"))"#,
+                ),
+                ("map.txt", "t..t\na++q\nt..t\n"),
+                ("waves.ron", WAVES),
+                (
+                    "enemies.ron",
+                    "{Circle: (armor: 2, speed: 0.5, regeneration: 0, health: 2.0)}",
+                ),
+            ],
+        );
+        assert!(package.is_valid(), "{:?}", package.errors);
+        assert_eq!(package.id(), "winter");
+        assert_eq!(package.content.map.unwrap().width, 4);
+        assert_eq!(package.content.waves.unwrap().waves.len(), 2);
+        assert_eq!(package.content.enemies[&EnemyForm::Circle].armor, 2);
+    }
+
+    #[test]
+    fn broken_content_is_reported() {
+        let package = load_package(
+            "broken",
+            &[
+                (
+                    "mod.ron",
+                    r#"(id: "broken", name: "Broken", map: Some("map.txt"), waves: Some("waves.ron"), towers: Some("towers.ron"))"#,
+                ),
+                ("map.txt", "a+#q"),
+                ("waves.ron", "[]"),
+            ],
+        );
+        assert_eq!(package.errors.len(), 3, "{:?}", package.errors);
+        assert!(package.errors[0].starts_with("map.txt"));
+        assert!(package.errors[1].contains("no waves"));
+        assert!(package.errors[2].contains("towers.ron is missing"));
+        assert!(!load_package("empty", &[]).is_valid());
+    }
+
+    #[test]
+    fn weak_towers_are_reported() {
+        let package = load_package(
+            "slow",
+            &[
+                (
+                    "mod.ron",
+                    r#"(id: "slow", name: "Slow", towers: Some("towers.ron"))"#,
+                ),
+                (
+                    "towers.ron",
+                    "(range: 100., damage: 15, speed: 40., upgrade_range: 10., upgrade_damage: 5, upgrade_speed: -20.)",
+                ),
+            ],
+        );
+        assert_eq!(package.errors.len(), 2, "{:?}", package.errors);
+        assert!(package.errors[0].contains("speed has to be above"));
+        assert!(package.errors[1].contains("must not weaken"));
+    }
+
+    #[test]
+    fn first_mod_wins_a_conflict() {
+        let manifest =
+            |id: &str| format!(r#"(id: "{id}", name: "{id}", waves: Some("waves.ron"))"#);
+        let mut mods = Mods::new(vec![
+            load_package(
+                "a",
+                &[
+                    ("mod.ron", manifest("a").as_str()),
+                    ("waves.ron", "[(enemies: 1)]"),
+                ],
+            ),
+            load_package(
+                "b",
+                &[("mod.ron", manifest("b").as_str()), ("waves.ron", WAVES)],
+            ),
+            load_package(
+                "c",
+                &[("mod.ron", manifest("a").as_str()), ("waves.ron", WAVES)],
+            ),
+        ]);
+        assert!(!mods.packages[2].is_valid());
+
+        let registries = mods.merge(&["b".to_string(), "a".to_string()]);
+        assert_eq!(registries.waves.waves.len(), 1);
+        assert_eq!(mods.conflicts.len(), 1);
+
+        let registries = mods.merge(&["b".to_string()]);
+        assert_eq!(registries.waves.waves.len(), 2);
+        assert!(mods.conflicts.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::audio::MusicChannel;
+use crate::boss::Boss;
+use crate::enemies::{Enemy, Tameable};
+use crate::loading::AudioAssets;
+use crate::ui::GameState;
+use crate::AppState;
+use bevy::prelude::*;
+use bevy_kira_audio::{AudioChannel, AudioControl, AudioInstance, AudioSource, AudioTween};
+use std::time::Duration;
+
+/// Enemies on the map from which on the music gets busy
+const COMBAT_ENEMIES: usize = 6;
+/// Castle health at which the music turns tense
+const DANGER_HEALTH: usize = 5;
+/// Seconds the game has to stay calmer before the music follows. Escalating is immediate.
+const CALM_DOWN_DELAY: f32 = 4.;
+const FADE_TIME: Duration = Duration::from_secs(2);
+
+/// Crossfades between music layers depending on how intense the game currently is
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Menu), start_music)
+            .add_systems(
+                Update,
+                direct_music
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(resource_exists::<MusicDirector>()),
+            )
+            .add_systems(OnExit(AppState::InGame), calm_music);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Intensity {
+    #[default]
+    Calm,
+    Combat,
+    Danger,
+}
+
+impl Intensity {
+    pub fn of(enemies: usize, health: usize, boss: bool) -> Self {
+        if health < 1 {
+            Intensity::Calm
+        } else if health <= DANGER_HEALTH || boss {
+            Intensity::Danger
+        } else if enemies >= COMBAT_ENEMIES {
+            Intensity::Combat
+        } else {
+            Intensity::Calm
+        }
+    }
+
+    /// Volumes of the calm, combat and danger layers
+    fn layer_volumes(&self) -> [f64; 3] {
+        match self {
+            Intensity::Calm => [1., 0., 0.],
+            Intensity::Combat => [1., 1., 0.],
+            Intensity::Danger => [0.6, 0.6, 1.],
+        }
+    }
+}
+
+/// All layers keep looping, only their volumes change
+#[derive(Resource)]
+struct MusicDirector {
+    layers: [Handle<AudioInstance>; 3],
+    intensity: Intensity,
+    /// Seconds the game has been calmer than the music
+    calmer_for: f32,
+}
+
+impl MusicDirector {
+    fn fade_to(&mut self, intensity: Intensity, instances: &mut Assets<AudioInstance>) {
+        self.intensity = intensity;
+        self.calmer_for = 0.;
+        for (layer, volume) in self.layers.iter().zip(intensity.layer_volumes()) {
+            if let Some(instance) = instances.get_mut(layer) {
+                instance.set_volume(volume, AudioTween::linear(FADE_TIME));
+            }
+        }
+    }
+}
+
+fn start_music(
+    mut commands: Commands,
+    audio_assets: Res<AudioAssets>,
+    music_channel: Res<AudioChannel<MusicChannel>>,
+    director: Option<Res<MusicDirector>>,
+) {
+    // the menu is entered again coming back from the editor
+    if director.is_some() {
+        return;
+    }
+    let [calm, combat, danger] = Intensity::Calm.layer_volumes();
+    let play = |source: &Handle<AudioSource>, volume: f64| {
+        music_channel
+            .play(source.clone())
+            .looped()
+            .with_volume(volume)
+            .handle()
+    };
+    commands.insert_resource(MusicDirector {
+        layers: [
+            play(&audio_assets.background, calm),
+            play(&audio_assets.music_combat, combat),
+            play(&audio_assets.music_danger, danger),
+        ],
+        intensity: Intensity::Calm,
+        calmer_for: 0.,
+    });
+}
+
+fn direct_music(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    enemy_query: Query<(), (With<Enemy>, Without<Tameable>)>,
+    boss_query: Query<(), (With<Boss>, Without<Tameable>)>,
+    mut director: ResMut<MusicDirector>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    let intensity = Intensity::of(
+        enemy_query.iter().count(),
+        game_state.health,
+        !boss_query.is_empty(),
+    );
+    if intensity > director.intensity {
+        director.fade_to(intensity, &mut instances);
+    } else if intensity < director.intensity {
+        // real time, so pausing the game does not calm the music down
+        director.calmer_for += time.raw_delta_seconds();
+        if director.calmer_for >= CALM_DOWN_DELAY {
+            director.fade_to(intensity, &mut instances);
+        }
+    } else {
+        director.calmer_for = 0.;
+    }
+}
+
+/// Leaving the game fades back to the calm layer instead of cutting the music
+fn calm_music(
+    director: Option<ResMut<MusicDirector>>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    if let Some(mut director) = director {
+        director.fade_to(Intensity::Calm, &mut instances);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intensity_follows_the_game() {
+        assert_eq!(Intensity::of(0, 20, false), Intensity::Calm);
+        assert_eq!(Intensity::of(COMBAT_ENEMIES, 20, false), Intensity::Combat);
+        assert_eq!(Intensity::of(1, 20, true), Intensity::Danger);
+        assert_eq!(Intensity::of(0, DANGER_HEALTH, false), Intensity::Danger);
+        assert_eq!(Intensity::of(COMBAT_ENEMIES, 0, true), Intensity::Calm);
+    }
+}
@@ -1,34 +1,68 @@
+use crate::actions::{Action, VirtualCursor};
+use crate::boss::Wildcard;
+use crate::bot::Bot;
 use crate::enemies::{Enemy, EnemyColor, EnemyForm, Tameable};
 use crate::map::{Coordinate, Map, Tile};
+use crate::recipes::{Recipe, RECIPES};
+use crate::settings::{Palette, Settings};
+use crate::spatial::{update_enemy_grids, TameableGrid};
+use crate::statistics::RunStatistics;
+use crate::towers::SellTower;
+use crate::ui::GameState;
 use crate::{AppState, ENEMY_Z, PUZZLE_Z};
 use bevy::prelude::*;
 use bevy::window::PrimaryWindow;
 use bevy_prototype_lyon::entity::ShapeBundle;
 use bevy_prototype_lyon::prelude::*;
 use bevy_prototype_lyon::shapes::Circle;
-use rand::random;
+use rand::distributions::Standard;
+use rand::prelude::*;
+
+const REROLL_BASE_COST: usize = 10;
+/// Distance from the cursor in which a piece can be picked up
+const PICK_RADIUS: f32 = 16.;
+/// Distance from a slot in which a reroll click hits its puzzle
+const REROLL_RADIUS: f32 = 12.;
+/// Dropped pieces snap into the closest matching slot within this distance
+const SNAP_DISTANCE: f32 = 28.;
+const WRONG_SLOT_COLOR: Color = Color::rgb(0.9, 0.1, 0.1);
+/// Chance for a new puzzle to ask for the pieces of a recipe
+const RECIPE_PUZZLE_CHANCE: f64 = 0.25;
 
 pub struct PuzzlePlugin;
 
 impl Plugin for PuzzlePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(PuzzleIdFactory::default())
-            .insert_resource(PickSource {
-                cursor_offset: Vec2::new(17., -19.),
-                ..Default::default()
-            })
+            .init_resource::<PickSource>()
             .insert_resource(CurrentPiece {
                 entity: None,
                 piece: None,
+                wildcard: false,
             })
             .add_event::<CompletePuzzle>()
+            .add_event::<RerollPuzzle>()
+            .add_event::<PiecePickedUp>()
+            .add_event::<PiecePlaced>()
+            .add_event::<WrongSlot>()
             .insert_resource(Puzzles { towers: vec![] })
             .add_systems(OnEnter(AppState::InGame), set_tower_puzzles)
             .add_systems(
                 Update,
                 (
-                    update_picked_up_piece,
-                    (puzzle_input, place_puzzle_piece, update_puzzle).chain(),
+                    update_pick_source.before(puzzle_input),
+                    update_picked_up_piece.after(puzzle_input),
+                    highlight_slots.after(puzzle_input),
+                    shake_slots,
+                    (
+                        puzzle_input.after(update_enemy_grids),
+                        reroll_input,
+                        reroll_puzzles,
+                        place_puzzle_piece,
+                        update_puzzle,
+                        reset_sold_puzzles,
+                    )
+                        .chain(),
                 )
                     .run_if(in_state(AppState::InGame)),
             )
@@ -36,9 +70,27 @@ impl Plugin for PuzzlePlugin {
     }
 }
 
+#[derive(Event)]
+pub struct PiecePickedUp;
+
+#[derive(Event)]
+pub struct PiecePlaced;
+
+/// A piece was dropped onto a slot it does not fit
+#[derive(Event)]
+pub struct WrongSlot;
+
 #[derive(Debug, Event)]
 pub struct CompletePuzzle {
     pub coordinate: Coordinate,
+    pub recipe: Option<Recipe>,
+    /// Most common color of the pieces
+    pub color: EnemyColor,
+    puzzle_id: usize,
+}
+
+#[derive(Debug, Event)]
+pub struct RerollPuzzle {
     puzzle_id: usize,
 }
 
@@ -56,48 +108,101 @@ impl PuzzleIdFactory {
 
 #[derive(Clone, Component)]
 pub struct PuzzleSlot {
-    piece: Piece,
-    filled: bool,
-    puzzle_id: usize,
+    pub(crate) piece: Piece,
+    pub(crate) filled: bool,
+    pub(crate) puzzle_id: usize,
+    pub(crate) index: usize,
 }
 
 #[derive(Resource)]
 pub struct CurrentPiece {
     pub entity: Option<Entity>,
     pub piece: Option<Piece>,
+    /// A wildcard fills all open slots of a puzzle
+    pub wildcard: bool,
+}
+
+impl CurrentPiece {
+    fn fits(&self, slot: &PuzzleSlot) -> bool {
+        !slot.filled && (self.wildcard || self.piece.as_ref() == Some(&slot.piece))
+    }
+
+    fn release(&mut self) {
+        self.entity = None;
+        self.piece = None;
+        self.wildcard = false;
+    }
 }
 
 #[derive(Default, Resource)]
 pub struct PickSource {
     pub last_cursor_pos: Vec2,
-    pub cursor_offset: Vec2,
+    pub just_pressed: bool,
+    pub just_released: bool,
+    /// Drop the carried piece where it is
+    pub just_canceled: bool,
 }
 
 #[derive(Resource)]
 pub struct Puzzles {
-    towers: Vec<Puzzle>,
+    pub(crate) towers: Vec<Puzzle>,
 }
 
 pub struct Puzzle {
-    id: usize,
-    coordinate: Coordinate,
+    pub(crate) id: usize,
+    pub(crate) coordinate: Coordinate,
     pieces: [Piece; 4],
     filled: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Piece {
-    color: EnemyColor,
-    form: EnemyForm,
+    pub color: EnemyColor,
+    pub form: EnemyForm,
 }
 
+impl Distribution<Piece> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Piece {
+        Piece {
+            color: rng.gen(),
+            form: rng.gen(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct ToFill;
+
 #[derive(Component)]
-struct ToFill;
+pub(crate) struct Shake {
+    timer: Timer,
+    origin: Vec3,
+}
+
+fn dominant_color(pieces: &[Piece; 4]) -> EnemyColor {
+    pieces
+        .iter()
+        .max_by_key(|piece| {
+            pieces
+                .iter()
+                .filter(|other| other.color == piece.color)
+                .count()
+        })
+        .unwrap()
+        .color
+        .clone()
+}
+
+/// Gold needed for the next reroll. Every reroll in a run doubles the price.
+pub fn reroll_cost(rerolls: usize) -> usize {
+    REROLL_BASE_COST * 2usize.pow(rerolls.min(16) as u32)
+}
 
 fn set_tower_puzzles(
     mut commands: Commands,
     mut puzzles: ResMut<Puzzles>,
     map: Res<Map>,
+    settings: Res<Settings>,
     mut puzzle_ids: ResMut<PuzzleIdFactory>,
 ) {
     let mut tower_positions: Vec<Coordinate> = vec![];
@@ -114,73 +219,80 @@ fn set_tower_puzzles(
 
     for coordinate in tower_positions {
         let id = puzzle_ids.get_next_id();
-        let puzzle = spawn_puzzle(id, coordinate, &mut commands);
+        let puzzle = spawn_puzzle(id, coordinate, settings.palette, &mut commands);
 
         puzzles.towers.push(puzzle);
     }
 }
 
-fn spawn_puzzle(id: usize, coordinate: Coordinate, commands: &mut Commands) -> Puzzle {
+fn spawn_puzzle(
+    id: usize,
+    coordinate: Coordinate,
+    palette: Palette,
+    commands: &mut Commands,
+) -> Puzzle {
+    let mut rng = thread_rng();
+    let pieces = if rng.gen_bool(RECIPE_PUZZLE_CHANCE) {
+        RECIPES.choose(&mut rng).unwrap().generate_pieces(&mut rng)
+    } else {
+        [rng.gen(), rng.gen(), rng.gen(), rng.gen()]
+    };
     let puzzle = Puzzle {
         coordinate: coordinate.clone(),
         filled: 0,
         id,
-        pieces: [
-            Piece {
-                color: random(),
-                form: random(),
-            },
-            Piece {
-                color: random(),
-                form: random(),
-            },
-            Piece {
-                color: random(),
-                form: random(),
-            },
-            Piece {
-                color: random(),
-                form: random(),
-            },
-        ],
+        pieces,
     };
     for (index, piece) in puzzle.pieces.iter().enumerate() {
-        let coordinate = match index {
-            0 => Coordinate {
-                x: coordinate.x - 16.,
-                y: coordinate.y - 16.,
-            },
-            1 => Coordinate {
-                x: coordinate.x + 16.,
-                y: coordinate.y - 16.,
-            },
-            2 => Coordinate {
-                x: coordinate.x + 16.,
-                y: coordinate.y + 16.,
-            },
-            _ => Coordinate {
-                x: coordinate.x - 16.,
-                y: coordinate.y + 16.,
-            },
-        };
-
-        let bundle = piece.form.build_bundle(
-            Transform::from_translation(Vec3::new(coordinate.x, coordinate.y, PUZZLE_Z)),
-            piece.color.to_color(),
-            None,
-        );
-        commands.spawn(bundle).insert(PuzzleSlot {
-            piece: piece.clone(),
-            filled: false,
-            puzzle_id: id,
-        });
+        spawn_puzzle_slot(id, index, &coordinate, piece.clone(), palette, commands);
     }
     puzzle
 }
 
+fn spawn_puzzle_slot(
+    puzzle_id: usize,
+    index: usize,
+    puzzle_coordinate: &Coordinate,
+    piece: Piece,
+    palette: Palette,
+    commands: &mut Commands,
+) {
+    let coordinate = match index {
+        0 => Coordinate {
+            x: puzzle_coordinate.x - 16.,
+            y: puzzle_coordinate.y - 16.,
+        },
+        1 => Coordinate {
+            x: puzzle_coordinate.x + 16.,
+            y: puzzle_coordinate.y - 16.,
+        },
+        2 => Coordinate {
+            x: puzzle_coordinate.x + 16.,
+            y: puzzle_coordinate.y + 16.,
+        },
+        _ => Coordinate {
+            x: puzzle_coordinate.x - 16.,
+            y: puzzle_coordinate.y + 16.,
+        },
+    };
+
+    let bundle = piece.form.build_bundle(
+        Transform::from_translation(coordinate.to_translation(PUZZLE_Z)),
+        piece.color.to_color(palette),
+        None,
+    );
+    commands.spawn(bundle).insert(PuzzleSlot {
+        piece,
+        filled: false,
+        puzzle_id,
+        index,
+    });
+}
+
 fn place_puzzle_piece(
     mut commands: Commands,
     mut puzzles: ResMut<Puzzles>,
+    settings: Res<Settings>,
     mut query: Query<(Entity, &mut Fill, &mut PuzzleSlot), With<ToFill>>,
     mut complete_puzzle: EventWriter<CompletePuzzle>,
 ) {
@@ -194,86 +306,268 @@ fn place_puzzle_piece(
         if puzzle.filled == 4 {
             complete_puzzle.send(CompletePuzzle {
                 coordinate: puzzle.coordinate.clone(),
+                recipe: Recipe::matching(&puzzle.pieces),
+                color: dominant_color(&puzzle.pieces),
                 puzzle_id: puzzle.id,
             });
             continue;
         }
 
         commands.entity(entity).remove::<ToFill>();
-        fill.color = slot.piece.color.to_color();
+        fill.color = slot.piece.color.to_color(settings.palette);
         slot.filled = true;
     }
 }
 
-fn puzzle_input(
-    mut commands: Commands,
-    mouse_button_inputs: Res<Input<MouseButton>>,
-    mut tamable_query: Query<(Entity, &mut Transform, &Enemy), With<Tameable>>,
-    mut puzzle_query: Query<(Entity, &Transform, &mut PuzzleSlot), Without<Enemy>>,
+pub(crate) fn update_pick_source(
+    bot: Option<Res<Bot>>,
+    actions: Res<Input<Action>>,
+    virtual_cursor: Res<VirtualCursor>,
+    touches: Res<Touches>,
     window: Query<&Window, With<PrimaryWindow>>,
     camera: Query<(&Camera, &GlobalTransform)>,
-    mut currently_picked: ResMut<CurrentPiece>,
     mut pick_source: ResMut<PickSource>,
 ) {
-    let (camera, camera_transform) = camera.single();
-    let cursor_position = if let Some(world_position) = window
-        .single()
-        .cursor_position()
+    // a bot moves the pick source on its own, the mouse must not pull pieces away from it
+    if bot.is_some() {
+        return;
+    }
+    // headless runs have neither a window nor a camera
+    let (Ok(window), Ok((camera, camera_transform))) = (window.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    // a finished touch is no longer "pressed", but we still need its position to drop the piece
+    let touch_position = touches
+        .iter()
+        .chain(touches.iter_just_released())
+        .next()
+        .map(|touch| touch.position());
+    if virtual_cursor.active && touch_position.is_none() {
+        pick_source.last_cursor_pos = virtual_cursor.position;
+    } else if let Some(world_position) = touch_position
+        .or_else(|| window.cursor_position())
         .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
         .map(|ray| ray.origin.truncate())
     {
-        world_position
-    } else {
-        pick_source.last_cursor_pos
-    };
-    pick_source.last_cursor_pos = cursor_position;
-    if mouse_button_inputs.just_pressed(MouseButton::Left) {
-        if currently_picked.entity.is_none() {
-            for (entity, transform, enemy) in tamable_query.iter_mut() {
-                if Vec2::new(
-                    transform.translation.x - cursor_position.x,
-                    transform.translation.y - cursor_position.y,
-                )
-                .length()
-                    < 12.
-                {
-                    currently_picked.entity = Some(entity);
-                    currently_picked.piece = Some(Piece {
-                        form: enemy.form.clone(),
-                        color: enemy.color.clone(),
-                    });
-                    return;
-                }
+        pick_source.last_cursor_pos = world_position;
+    }
+    pick_source.just_pressed =
+        actions.just_pressed(Action::PickUp) || touches.iter_just_pressed().next().is_some();
+    pick_source.just_released =
+        actions.just_released(Action::Place) || touches.iter_just_released().next().is_some();
+    pick_source.just_canceled = actions.just_pressed(Action::Release);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn puzzle_input(
+    mut commands: Commands,
+    tamable_query: Query<(Entity, &Enemy, Option<&Wildcard>), With<Tameable>>,
+    tameable_grid: Res<TameableGrid>,
+    mut puzzle_query: Query<(Entity, &Transform, &mut PuzzleSlot), Without<Enemy>>,
+    shake_query: Query<&Shake>,
+    mut currently_picked: ResMut<CurrentPiece>,
+    pick_source: Res<PickSource>,
+    mut picked_up: EventWriter<PiecePickedUp>,
+    mut placed: EventWriter<PiecePlaced>,
+    mut wrong_slot: EventWriter<WrongSlot>,
+) {
+    let cursor_position = pick_source.last_cursor_pos;
+    if pick_source.just_pressed && currently_picked.entity.is_none() {
+        let closest_tameable = tameable_grid
+            .closest(cursor_position, PICK_RADIUS)
+            .and_then(|(entity, _)| tamable_query.get(entity).ok());
+        if let Some((entity, enemy, wildcard)) = closest_tameable {
+            currently_picked.entity = Some(entity);
+            currently_picked.piece = Some(Piece {
+                form: enemy.form.clone(),
+                color: enemy.color.clone(),
+            });
+            currently_picked.wildcard = wildcard.is_some();
+            picked_up.send(PiecePickedUp);
+        }
+        return;
+    }
+    if pick_source.just_canceled {
+        currently_picked.release();
+        return;
+    }
+    if !pick_source.just_released || currently_picked.entity.is_none() {
+        return;
+    }
+
+    // we have a piece, snap it into the closest matching slot or let it go
+    let piece_entity = currently_picked.entity.unwrap();
+    let closest_slot = puzzle_query
+        .iter()
+        .filter(|(_, _, slot)| currently_picked.fits(slot))
+        .map(|(entity, transform, slot)| {
+            (
+                entity,
+                transform.translation.truncate().distance(cursor_position),
+                slot.puzzle_id,
+            )
+        })
+        .filter(|(_, distance, _)| *distance < SNAP_DISTANCE)
+        .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b));
+    if let Some((slot_entity, _, puzzle_id)) = closest_slot {
+        commands.entity(piece_entity).despawn();
+        for (entity, _, mut slot) in puzzle_query.iter_mut() {
+            let fill_slot = if currently_picked.wildcard {
+                slot.puzzle_id == puzzle_id && !slot.filled
+            } else {
+                entity == slot_entity
+            };
+            if fill_slot {
+                commands.entity(entity).insert(ToFill);
+                slot.filled = true;
             }
+        }
+        placed.send(PiecePlaced);
+        currently_picked.release();
+        return;
+    }
+    // dropped onto a slot that does not fit
+    for (slot_entity, transform, slot) in puzzle_query.iter() {
+        if !slot.filled && transform.translation.truncate().distance(cursor_position) < PICK_RADIUS
+        {
+            // a slot that is still shaking is already displaced
+            let origin = shake_query
+                .get(slot_entity)
+                .map_or(transform.translation, |shake| shake.origin);
+            commands.entity(slot_entity).insert(Shake {
+                timer: Timer::from_seconds(0.3, TimerMode::Once),
+                origin,
+            });
+            wrong_slot.send(WrongSlot);
+        }
+    }
+    // go free my friend
+    currently_picked.release();
+}
+
+/// Highlight the slots that fit the carried piece and tint the one under the cursor if it does not fit
+fn highlight_slots(
+    settings: Res<Settings>,
+    currently_picked: Res<CurrentPiece>,
+    pick_source: Res<PickSource>,
+    mut slot_query: Query<(&Transform, &PuzzleSlot, &mut Fill, &mut Stroke)>,
+) {
+    for (transform, slot, mut fill, mut stroke) in slot_query.iter_mut() {
+        if slot.filled {
+            continue;
+        }
+        let mut fill_color = Color::NONE;
+        let mut stroke_color = slot.piece.color.to_color(settings.palette);
+        if currently_picked.entity.is_some() {
+            if currently_picked.fits(slot) {
+                fill_color = slot.piece.color.to_color(settings.palette).with_a(0.4);
+            } else if transform
+                .translation
+                .truncate()
+                .distance(pick_source.last_cursor_pos)
+                < PICK_RADIUS
+            {
+                stroke_color = WRONG_SLOT_COLOR;
+            }
+        }
+        if fill.color != fill_color {
+            fill.color = fill_color;
+        }
+        if stroke.color != stroke_color {
+            stroke.color = stroke_color;
+        }
+    }
+}
+
+fn shake_slots(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut slot_query: Query<(Entity, &mut Transform, &mut Shake)>,
+) {
+    for (entity, mut transform, mut shake) in slot_query.iter_mut() {
+        shake.timer.tick(time.delta());
+        if shake.timer.finished() {
+            transform.translation = shake.origin;
+            commands.entity(entity).remove::<Shake>();
         } else {
-            // we have a piece, place it in a puzzle or let it go
-            let mut found_slot: bool = false;
-            for (puzzle_entity, transform, mut slot) in puzzle_query.iter_mut() {
-                if slot.filled
-                    || Vec2::new(
-                        transform.translation.x - cursor_position.x,
-                        transform.translation.y - cursor_position.y,
-                    )
-                    .length()
-                        > 12.
-                {
-                    continue;
-                }
-                found_slot = true;
-                if &slot.piece == currently_picked.piece.as_ref().unwrap() {
-                    commands.entity(currently_picked.entity.unwrap()).despawn();
-                    commands.entity(puzzle_entity).insert(ToFill);
-                    slot.filled = true;
-                    currently_picked.entity = None;
-                    currently_picked.piece = None;
-                    return;
-                }
+            let offset = (shake.timer.elapsed_secs() * 60.).sin() * 3.;
+            transform.translation = shake.origin + Vec3::new(offset, 0., 0.);
+        }
+    }
+}
+
+fn reroll_input(
+    actions: Res<Input<Action>>,
+    pick_source: Res<PickSource>,
+    puzzle_query: Query<(&Transform, &PuzzleSlot)>,
+    mut reroll_puzzle: EventWriter<RerollPuzzle>,
+) {
+    if !actions.just_pressed(Action::Reroll) {
+        return;
+    }
+    let cursor_position = pick_source.last_cursor_pos;
+    if let Some((_, slot)) = puzzle_query.iter().find(|(transform, _)| {
+        transform.translation.truncate().distance(cursor_position) < REROLL_RADIUS
+    }) {
+        reroll_puzzle.send(RerollPuzzle {
+            puzzle_id: slot.puzzle_id,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reroll_puzzles(
+    mut commands: Commands,
+    mut reroll_reader: EventReader<RerollPuzzle>,
+    mut puzzles: ResMut<Puzzles>,
+    mut game_state: ResMut<GameState>,
+    mut statistics: ResMut<RunStatistics>,
+    mut currently_picked: ResMut<CurrentPiece>,
+    settings: Res<Settings>,
+    slot_query: Query<(Entity, &PuzzleSlot)>,
+) {
+    for reroll in reroll_reader.iter() {
+        if game_state.health < 1 {
+            continue;
+        }
+        let Some(puzzle) = puzzles
+            .towers
+            .iter_mut()
+            .find(|puzzle| puzzle.id == reroll.puzzle_id)
+        else {
+            continue;
+        };
+        if let Some(piece_entity) = currently_picked.entity {
+            // a tamed piece in hand pays for the reroll
+            commands.entity(piece_entity).despawn();
+            currently_picked.release();
+            statistics.pieces_spent_on_rerolls += 1;
+        } else {
+            let cost = reroll_cost(statistics.rerolls);
+            if game_state.gold < cost {
+                continue;
             }
-            if !found_slot {
-                // go free my friend
-                currently_picked.entity = None;
-                currently_picked.piece = None;
+            game_state.gold -= cost;
+            statistics.gold_spent_on_rerolls += cost;
+        }
+        statistics.rerolls += 1;
+
+        for (entity, slot) in slot_query.iter() {
+            if slot.puzzle_id != puzzle.id || slot.filled {
+                continue;
             }
+            commands.entity(entity).despawn();
+            let piece: Piece = random();
+            puzzle.pieces[slot.index] = piece.clone();
+            spawn_puzzle_slot(
+                puzzle.id,
+                slot.index,
+                &puzzle.coordinate,
+                piece,
+                settings.palette,
+                &mut commands,
+            );
         }
     }
 }
@@ -319,27 +613,82 @@ fn update_puzzle(
     mut puzzles: ResMut<Puzzles>,
     mut my_event_reader: EventReader<CompletePuzzle>,
     slot_query: Query<(Entity, &PuzzleSlot)>,
+    settings: Res<Settings>,
     mut puzzle_ids: ResMut<PuzzleIdFactory>,
 ) {
     for completed_puzzle in my_event_reader.iter() {
-        let puzzle_id = completed_puzzle.puzzle_id;
-        puzzles.towers = puzzles
+        replace_puzzle(
+            completed_puzzle.puzzle_id,
+            completed_puzzle.coordinate.clone(),
+            &mut commands,
+            &mut puzzles,
+            &slot_query,
+            &mut puzzle_ids,
+            settings.palette,
+        );
+    }
+}
+
+/// A sold tower plot starts over with a fresh build puzzle
+fn reset_sold_puzzles(
+    mut commands: Commands,
+    mut puzzles: ResMut<Puzzles>,
+    mut sell_reader: EventReader<SellTower>,
+    slot_query: Query<(Entity, &PuzzleSlot)>,
+    settings: Res<Settings>,
+    mut puzzle_ids: ResMut<PuzzleIdFactory>,
+) {
+    for sold_tower in sell_reader.iter() {
+        let Some(puzzle_id) = puzzles
             .towers
-            .drain(..)
-            .filter(|puzzle| puzzle.id != puzzle_id)
-            .collect();
-        for (entity, slot) in slot_query.iter() {
-            if slot.puzzle_id == puzzle_id {
-                commands.entity(entity).despawn();
-            }
+            .iter()
+            .find(|puzzle| puzzle.coordinate == sold_tower.coordinate)
+            .map(|puzzle| puzzle.id)
+        else {
+            continue;
+        };
+        replace_puzzle(
+            puzzle_id,
+            sold_tower.coordinate.clone(),
+            &mut commands,
+            &mut puzzles,
+            &slot_query,
+            &mut puzzle_ids,
+            settings.palette,
+        );
+    }
+}
+
+fn replace_puzzle(
+    puzzle_id: usize,
+    coordinate: Coordinate,
+    commands: &mut Commands,
+    puzzles: &mut Puzzles,
+    slot_query: &Query<(Entity, &PuzzleSlot)>,
+    puzzle_ids: &mut PuzzleIdFactory,
+    palette: Palette,
+) {
+    puzzles.towers = puzzles
+        .towers
+        .drain(..)
+        .filter(|puzzle| puzzle.id != puzzle_id)
+        .collect();
+    for (entity, slot) in slot_query.iter() {
+        if slot.puzzle_id == puzzle_id {
+            commands.entity(entity).despawn();
         }
-        let id = puzzle_ids.get_next_id();
-        let puzzle = spawn_puzzle(id, completed_puzzle.coordinate.clone(), &mut commands);
-        puzzles.towers.push(puzzle);
     }
+    let id = puzzle_ids.get_next_id();
+    let puzzle = spawn_puzzle(id, coordinate, palette, commands);
+    puzzles.towers.push(puzzle);
 }
 
-fn break_down_puzzles(mut commands: Commands, puzzle_slot_query: Query<Entity, With<PuzzleSlot>>) {
+fn break_down_puzzles(
+    mut commands: Commands,
+    mut puzzles: ResMut<Puzzles>,
+    puzzle_slot_query: Query<Entity, With<PuzzleSlot>>,
+) {
+    puzzles.towers.clear();
     for entity in puzzle_slot_query.iter() {
         commands.entity(entity).despawn();
     }
@@ -0,0 +1,154 @@
+use crate::enemies::{EnemyColor, EnemyForm};
+use crate::puzzle::Piece;
+use crate::towers::Specialization;
+use rand::prelude::*;
+
+/// Recipes in the order they are checked. More specific recipes come first,
+/// so four red triangles count as `FourReds` and not as `AllTriangles`.
+pub const RECIPES: [Recipe; 4] = [
+    Recipe::FourReds,
+    Recipe::AllTriangles,
+    Recipe::OneOfEachForm,
+    Recipe::AllSameForm,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recipe {
+    FourReds,
+    AllTriangles,
+    OneOfEachForm,
+    AllSameForm,
+}
+
+impl Recipe {
+    pub fn matching(pieces: &[Piece; 4]) -> Option<Recipe> {
+        RECIPES
+            .iter()
+            .copied()
+            .find(|recipe| recipe.matches(pieces))
+    }
+
+    pub fn matches(&self, pieces: &[Piece; 4]) -> bool {
+        match self {
+            Recipe::FourReds => pieces.iter().all(|piece| piece.color == EnemyColor::Red),
+            Recipe::AllTriangles => pieces.iter().all(|piece| piece.form == EnemyForm::Triangle),
+            Recipe::OneOfEachForm => [EnemyForm::Circle, EnemyForm::Triangle, EnemyForm::Quadratic]
+                .iter()
+                .all(|form| pieces.iter().any(|piece| &piece.form == form)),
+            Recipe::AllSameForm => pieces.iter().all(|piece| piece.form == pieces[0].form),
+        }
+    }
+
+    pub fn specialization(&self) -> Specialization {
+        match self {
+            Recipe::FourReds => Specialization::Fire,
+            Recipe::AllTriangles => Specialization::Sniper,
+            Recipe::OneOfEachForm => Specialization::Rapid,
+            Recipe::AllSameForm => Specialization::Heavy,
+        }
+    }
+
+    /// Random pieces that fulfill this recipe
+    pub fn generate_pieces<R: Rng + ?Sized>(&self, rng: &mut R) -> [Piece; 4] {
+        let mut pieces: [Piece; 4] = [rng.gen(), rng.gen(), rng.gen(), rng.gen()];
+        match self {
+            Recipe::FourReds => {
+                for piece in pieces.iter_mut() {
+                    piece.color = EnemyColor::Red;
+                }
+            }
+            Recipe::AllTriangles => {
+                for piece in pieces.iter_mut() {
+                    piece.form = EnemyForm::Triangle;
+                }
+            }
+            Recipe::OneOfEachForm => {
+                pieces[0].form = EnemyForm::Circle;
+                pieces[1].form = EnemyForm::Triangle;
+                pieces[2].form = EnemyForm::Quadratic;
+                pieces.shuffle(rng);
+            }
+            Recipe::AllSameForm => {
+                let form: EnemyForm = rng.gen();
+                for piece in pieces.iter_mut() {
+                    piece.form = form.clone();
+                }
+            }
+        }
+        pieces
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    fn pieces(pieces: [(EnemyColor, EnemyForm); 4]) -> [Piece; 4] {
+        pieces.map(|(color, form)| Piece { color, form })
+    }
+
+    #[test]
+    fn specific_recipes_take_priority() {
+        use EnemyColor::*;
+        use EnemyForm::*;
+        assert_eq!(
+            Recipe::matching(&pieces([
+                (Red, Triangle),
+                (Red, Triangle),
+                (Red, Triangle),
+                (Red, Triangle)
+            ])),
+            Some(Recipe::FourReds)
+        );
+        assert_eq!(
+            Recipe::matching(&pieces([
+                (Red, Triangle),
+                (Blue, Triangle),
+                (Red, Triangle),
+                (Red, Triangle)
+            ])),
+            Some(Recipe::AllTriangles)
+        );
+        assert_eq!(
+            Recipe::matching(&pieces([
+                (Red, Circle),
+                (Red, Triangle),
+                (Red, Quadratic),
+                (Blue, Circle)
+            ])),
+            Some(Recipe::OneOfEachForm)
+        );
+        assert_eq!(
+            Recipe::matching(&pieces([
+                (Red, Circle),
+                (Green, Circle),
+                (Pink, Circle),
+                (Lilac, Circle)
+            ])),
+            Some(Recipe::AllSameForm)
+        );
+        assert_eq!(
+            Recipe::matching(&pieces([
+                (Red, Circle),
+                (Green, Circle),
+                (Pink, Triangle),
+                (Lilac, Triangle)
+            ])),
+            None
+        );
+    }
+
+    #[test]
+    fn generated_pieces_fulfill_their_recipe() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for recipe in RECIPES {
+            for _ in 0..20 {
+                assert!(
+                    recipe.matches(&recipe.generate_pieces(&mut rng)),
+                    "{recipe:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use crate::loading::{available_themes, FontAssets, DEFAULT_THEME};
+use crate::menu::spawn_main_menu;
+use crate::ui::ButtonColors;
+use crate::AppState;
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowMode};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_PATH: &str = "config/settings.ron";
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "oicana_settings";
+const RESOLUTIONS: [(f32, f32); 4] = [(800., 600.), (1024., 768.), (1280., 720.), (1920., 1080.)];
+
+/// Applies and persists [`Settings`]. They are loaded in `main` already, so the window starts out right.
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<Settings>() {
+            app.insert_resource(Settings::load());
+        }
+        app.add_systems(Update, apply_settings)
+            .add_systems(Update, click_settings.run_if(in_state(AppState::Menu)));
+    }
+}
+
+#[derive(Clone, Debug, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f64,
+    pub music_volume: f64,
+    pub sfx_volume: f64,
+    pub ui_volume: f64,
+    pub window_mode: WindowModeSetting,
+    pub resolution: (f32, f32),
+    pub msaa: bool,
+    /// Show a health bar above every enemy
+    pub health_bars: bool,
+    pub palette: Palette,
+    /// Name of the asset manifest in `assets/themes`, applied on the next start
+    pub theme: String,
+    /// Ids of the mods to load, see [`crate::mods`]
+    pub enabled_mods: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.,
+            music_volume: 0.15,
+            sfx_volume: 0.15,
+            ui_volume: 0.15,
+            window_mode: WindowModeSetting::Windowed,
+            resolution: RESOLUTIONS[0],
+            msaa: true,
+            health_bars: false,
+            palette: Palette::Default,
+            theme: DEFAULT_THEME.to_string(),
+            enabled_mods: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WindowModeSetting {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl From<WindowModeSetting> for WindowMode {
+    fn from(mode: WindowModeSetting) -> Self {
+        match mode {
+            WindowModeSetting::Windowed => WindowMode::Windowed,
+            WindowModeSetting::Borderless => WindowMode::BorderlessFullscreen,
+            WindowModeSetting::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+/// Colors used for the enemy colors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Default,
+    /// Distinguishable with the common forms of color blindness
+    Colorblind,
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let Some(stored) = Settings::read() else {
+            return Settings::default();
+        };
+        ron::from_str(&stored).unwrap_or_else(|error| {
+            warn!("Failed to parse stored settings: {}", error);
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(serialized) => Settings::write(&serialized),
+            Err(error) => warn!("Failed to serialize settings: {}", error),
+        }
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        if self.msaa {
+            Msaa::Sample4
+        } else {
+            Msaa::Off
+        }
+    }
+
+    pub fn music_volume(&self) -> f64 {
+        self.master_volume * self.music_volume
+    }
+
+    pub fn sfx_volume(&self) -> f64 {
+        self.master_volume * self.sfx_volume
+    }
+
+    pub fn ui_volume(&self) -> f64 {
+        self.master_volume * self.ui_volume
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read() -> Option<String> {
+        std::fs::read_to_string(SETTINGS_PATH).ok()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write(serialized: &str) {
+        if let Err(error) = std::fs::write(SETTINGS_PATH, serialized) {
+            warn!("Failed to write {}: {}", SETTINGS_PATH, error);
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read() -> Option<String> {
+        Settings::local_storage()?.get_item(STORAGE_KEY).ok()?
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn write(serialized: &str) {
+        if let Some(storage) = Settings::local_storage() {
+            if storage.set_item(STORAGE_KEY, serialized).is_err() {
+                warn!("Failed to write settings to the local storage");
+            }
+        }
+    }
+}
+
+fn apply_settings(
+    settings: Res<Settings>,
+    mut msaa: ResMut<Msaa>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    *msaa = settings.msaa();
+    for mut window in window_query.iter_mut() {
+        window.mode = settings.window_mode.into();
+        window
+            .resolution
+            .set(settings.resolution.0, settings.resolution.1);
+    }
+}
+
+#[derive(Component)]
+struct SettingsScreen;
+
+/// A row of the settings screen. Clicking it cycles through the possible values.
+#[derive(Clone, Copy, Component)]
+enum SettingsOption {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    UiVolume,
+    WindowMode,
+    Resolution,
+    Msaa,
+    HealthBars,
+    Palette,
+    Theme,
+    Back,
+}
+
+const OPTIONS: [SettingsOption; 11] = [
+    SettingsOption::MasterVolume,
+    SettingsOption::MusicVolume,
+    SettingsOption::SfxVolume,
+    SettingsOption::UiVolume,
+    SettingsOption::WindowMode,
+    SettingsOption::Resolution,
+    SettingsOption::Msaa,
+    SettingsOption::HealthBars,
+    SettingsOption::Palette,
+    SettingsOption::Theme,
+    SettingsOption::Back,
+];
+
+fn next_volume(volume: f64) -> f64 {
+    ((volume * 10.).round() + 1.) % 11. / 10.
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+impl SettingsOption {
+    fn label(&self, settings: &Settings) -> String {
+        match self {
+            SettingsOption::MasterVolume => {
+                format!("Volume: {:.0}%", settings.master_volume * 100.)
+            }
+            SettingsOption::MusicVolume => format!("Music: {:.0}%", settings.music_volume * 100.),
+            SettingsOption::SfxVolume => format!("Effects: {:.0}%", settings.sfx_volume * 100.),
+            SettingsOption::UiVolume => format!("Interface: {:.0}%", settings.ui_volume * 100.),
+            SettingsOption::WindowMode => format!("Window: {:?}", settings.window_mode),
+            SettingsOption::Resolution => {
+                format!(
+                    "Resolution: {}x{}",
+                    settings.resolution.0, settings.resolution.1
+                )
+            }
+            SettingsOption::Msaa => format!("Anti-aliasing: {}", on_off(settings.msaa)),
+            SettingsOption::HealthBars => format!("Health bars: {}", on_off(settings.health_bars)),
+            SettingsOption::Palette => format!("Colors: {:?}", settings.palette),
+            SettingsOption::Theme => format!("Theme: {} (restart)", settings.theme),
+            SettingsOption::Back => "Back".to_string(),
+        }
+    }
+
+    fn cycle(&self, settings: &mut Settings) {
+        match self {
+            SettingsOption::MasterVolume => {
+                settings.master_volume = next_volume(settings.master_volume)
+            }
+            SettingsOption::MusicVolume => {
+                settings.music_volume = next_volume(settings.music_volume)
+            }
+            SettingsOption::SfxVolume => settings.sfx_volume = next_volume(settings.sfx_volume),
+            SettingsOption::UiVolume => settings.ui_volume = next_volume(settings.ui_volume),
+            SettingsOption::WindowMode => {
+                settings.window_mode = match settings.window_mode {
+                    WindowModeSetting::Windowed => WindowModeSetting::Borderless,
+                    WindowModeSetting::Borderless => WindowModeSetting::Fullscreen,
+                    WindowModeSetting::Fullscreen => WindowModeSetting::Windowed,
+                }
+            }
+            SettingsOption::Resolution => {
+                let current = RESOLUTIONS
+                    .iter()
+                    .position(|resolution| *resolution == settings.resolution)
+                    .unwrap_or(0);
+                settings.resolution = RESOLUTIONS[(current + 1) % RESOLUTIONS.len()];
+            }
+            SettingsOption::Msaa => settings.msaa = !settings.msaa,
+            SettingsOption::HealthBars => settings.health_bars = !settings.health_bars,
+            SettingsOption::Palette => {
+                settings.palette = match settings.palette {
+                    Palette::Default => Palette::Colorblind,
+                    Palette::Colorblind => Palette::Default,
+                }
+            }
+            SettingsOption::Theme => {
+                let themes = available_themes();
+                let current = themes
+                    .iter()
+                    .position(|theme| *theme == settings.theme)
+                    .map_or(0, |index| index + 1);
+                settings.theme = themes[current % themes.len()].clone();
+            }
+            SettingsOption::Back => {}
+        }
+    }
+}
+
+pub(crate) fn spawn_settings_screen(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    settings: &Settings,
+) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(SettingsScreen)
+        .with_children(|parent| {
+            for option in OPTIONS {
+                parent
+                    .spawn(ButtonBundle {
+                        style: Style {
+                            width: Val::Px(320.0),
+                            height: Val::Px(40.0),
+                            margin: UiRect::all(Val::Px(4.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        background_color: button_colors.normal.into(),
+                        ..Default::default()
+                    })
+                    .insert(option)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle {
+                            text: Text::from_section(
+                                option.label(settings),
+                                TextStyle {
+                                    font: font_assets.fira_sans.clone(),
+                                    font_size: 26.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+}
+
+fn click_settings(
+    mut commands: Commands,
+    mut settings: ResMut<Settings>,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            &SettingsOption,
+            &Children,
+        ),
+        Changed<Interaction>,
+    >,
+    mut text_query: Query<&mut Text>,
+    screen_query: Query<Entity, With<SettingsScreen>>,
+) {
+    for (interaction, mut color, option, children) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                if let SettingsOption::Back = option {
+                    settings.save();
+                    for screen in screen_query.iter() {
+                        commands.entity(screen).despawn_recursive();
+                    }
+                    spawn_main_menu(&mut commands, &font_assets, &button_colors);
+                    return;
+                }
+                option.cycle(&mut settings);
+                if let Ok(mut text) = text_query.get_mut(children[0]) {
+                    text.sections[0].value = option.label(&settings);
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume_cycles_in_steps_of_ten_percent() {
+        assert!((next_volume(0.15) - 0.3).abs() < 0.001);
+        assert!((next_volume(0.9) - 1.).abs() < 0.001);
+        assert_eq!(next_volume(1.), 0.);
+    }
+
+    #[test]
+    fn settings_survive_serialization() {
+        let settings = Settings {
+            palette: Palette::Colorblind,
+            health_bars: true,
+            ..Default::default()
+        };
+        let serialized = ron::to_string(&settings).unwrap();
+        let deserialized: Settings = ron::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.palette, Palette::Colorblind);
+        assert!(deserialized.health_bars);
+    }
+
+    #[test]
+    fn missing_fields_fall_back_to_defaults() {
+        let settings: Settings = ron::from_str("(health_bars: true)").unwrap();
+        assert!(settings.health_bars);
+        assert_eq!(settings.resolution, RESOLUTIONS[0]);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::abilities::AbilitiesPlugin;
+use crate::actions::ActionsPlugin;
+use crate::boss::BossPlugin;
+use crate::bot::{Bot, BotPlugin};
+use crate::bullets::{update_bullets, BulletPlugin};
+pub use crate::enemies::Tameable;
+use crate::enemies::{create_enemy, move_enemies, DamageEnemy, EnemiesPlugin, Enemy};
+use crate::loading::TextureAssets;
+pub use crate::map::{Coordinate, Map};
+use crate::map::{MapPlugin, Tile};
+use crate::puzzle::{PuzzlePlugin, PuzzleSlot, Puzzles, ToFill};
+use crate::settings::Settings;
+use crate::spatial::{update_enemy_grids, SpatialPlugin};
+use crate::statistics::StatisticsPlugin;
+use crate::status_effects::StatusEffectsPlugin;
+use crate::towers::{shoot, Tower, TowerBundle, TowerDefinition, TowerShot, TowersPlugin};
+pub use crate::ui::GameState;
+use crate::{AppState, ENEMY_Z};
+use bevy::ecs::system::CommandQueue;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use rand::prelude::*;
+use std::time::Duration;
+
+/// Time that passes with every simulated frame
+pub const FRAME_TIME: Duration = Duration::from_millis(16);
+
+/// Gameplay systems that can be run on their own, e.g. to measure them
+#[derive(Clone, Copy, Debug)]
+pub enum GameplaySystem {
+    MoveEnemies,
+    Shoot,
+    UpdateBullets,
+}
+
+impl GameplaySystem {
+    pub fn schedule(self) -> Schedule {
+        let mut schedule = Schedule::default();
+        match self {
+            GameplaySystem::MoveEnemies => schedule.add_systems(move_enemies),
+            GameplaySystem::Shoot => schedule.add_systems(
+                (
+                    update_enemy_grids,
+                    shoot,
+                    Events::<TowerShot>::update_system,
+                )
+                    .chain(),
+            ),
+            GameplaySystem::UpdateBullets => schedule.add_systems(
+                (
+                    update_enemy_grids,
+                    update_bullets,
+                    Events::<DamageEnemy>::update_system,
+                )
+                    .chain(),
+            ),
+        };
+        schedule
+    }
+}
+
+/// The game without rendering, audio, UI or asset loading. Every frame advances time by [`FRAME_TIME`].
+pub struct Simulation {
+    pub app: App,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Simulation::with_map(Map::load_map())
+    }
+}
+
+impl Simulation {
+    pub fn with_map(map: Map) -> Self {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME_TIME))
+            .insert_resource(stub_textures())
+            .insert_resource(GameState::default())
+            .insert_resource(Settings::default())
+            // usually registered by the window plugin
+            .add_event::<CursorMoved>()
+            .add_state::<AppState>()
+            .add_plugins((
+                MapPlugin,
+                EnemiesPlugin,
+                AbilitiesPlugin,
+                BossPlugin,
+                StatusEffectsPlugin,
+                TowersPlugin,
+                BulletPlugin,
+                PuzzlePlugin,
+                StatisticsPlugin,
+                SpatialPlugin,
+                BotPlugin,
+                ActionsPlugin,
+            ))
+            .insert_resource(map.gather_trees())
+            .insert_resource(map);
+
+        let mut simulation = Simulation { app };
+        // the menu renders the map tiles
+        simulation.enter(AppState::Menu);
+        simulation.enter(AppState::InGame);
+        simulation
+    }
+
+    /// A straight path from left to right with tower plots on both sides
+    pub fn synthetic_map(length: usize) -> Map {
+        let plots = ".".repeat(length);
+        let path = "+".repeat(length);
+        Map::parse(&format!("t{plots}t\na{path}q\nt{plots}t")).unwrap()
+    }
+
+    pub fn enter(&mut self, state: AppState) {
+        self.app
+            .world
+            .resource_mut::<NextState<AppState>>()
+            .set(state);
+        self.app.update();
+    }
+
+    pub fn step(&mut self) {
+        self.app.update();
+    }
+
+    pub fn step_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    /// Step until the condition holds. Returns false if it did not within the given number of frames.
+    pub fn step_until(
+        &mut self,
+        max_frames: usize,
+        mut condition: impl FnMut(&mut Simulation) -> bool,
+    ) -> bool {
+        for _ in 0..max_frames {
+            if condition(self) {
+                return true;
+            }
+            self.app.update();
+        }
+        condition(self)
+    }
+
+    /// Run a schedule on the world without advancing time
+    pub fn run_schedule(&mut self, schedule: &mut Schedule) {
+        schedule.run(&mut self.app.world);
+    }
+
+    /// Let the bot play the puzzles from now on
+    pub fn add_bot(&mut self, bot: Bot) {
+        self.app.insert_resource(bot);
+    }
+
+    pub fn filled_slots(&mut self) -> usize {
+        self.app
+            .world
+            .query::<&PuzzleSlot>()
+            .iter(&self.app.world)
+            .filter(|slot| slot.filled)
+            .count()
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        self.app.world.resource::<GameState>()
+    }
+
+    pub fn is_tameable(&self, entity: Entity) -> bool {
+        self.app.world.get::<Tameable>(entity).is_some()
+    }
+
+    /// Spawn enemies spread over the waypoints of the map
+    pub fn spawn_enemies(&mut self, count: usize, health: i32) -> Vec<Entity> {
+        let mut entities = vec![];
+        let mut rng = StdRng::seed_from_u64(count as u64);
+        let mut queue = CommandQueue::default();
+        {
+            let map = self.app.world.resource::<Map>();
+            let mut commands = Commands::new(&mut queue, &self.app.world);
+            for index in 0..count {
+                let waypoint = index % map.waypoints.len();
+                let position = if waypoint == 0 {
+                    &map.spawn
+                } else {
+                    &map.waypoints[waypoint - 1]
+                };
+                let mut enemy = Enemy::new(rng.gen(), rng.gen(), health);
+                enemy.current_waypoint_index = waypoint;
+                entities.push(create_enemy(
+                    &mut commands,
+                    enemy,
+                    Transform::from_translation(position.to_translation(ENEMY_Z)),
+                ));
+            }
+        }
+        queue.apply(&mut self.app.world);
+        entities
+    }
+
+    /// Spawn enemies until the given number is on the way to the castle
+    pub fn top_up_enemies(&mut self, count: usize, health: i32) {
+        let alive = self
+            .app
+            .world
+            .query_filtered::<(), (With<Enemy>, Without<Tameable>)>()
+            .iter(&self.app.world)
+            .count();
+        if alive < count {
+            self.spawn_enemies(count - alive, health);
+        }
+    }
+
+    pub fn tower_plots(&self) -> Vec<Coordinate> {
+        let map = self.app.world.resource::<Map>();
+        let mut plots: Vec<Coordinate> = vec![];
+        for (row_index, row) in map.tiles.iter().enumerate() {
+            for (column_index, tile) in row.iter().enumerate() {
+                if tile == &Tile::TowerPlot {
+                    plots.push(Coordinate {
+                        x: column_index as f32 * map.tile_size,
+                        y: row_index as f32 * map.tile_size,
+                    });
+                }
+            }
+        }
+        plots
+    }
+
+    /// Build level one towers on the first tower plots
+    pub fn spawn_towers(&mut self, count: usize) {
+        let definition = self.app.world.resource::<TowerDefinition>().clone();
+        for coordinate in self.tower_plots().into_iter().take(count) {
+            self.app
+                .world
+                .spawn(TowerBundle::new(coordinate, &definition));
+        }
+    }
+
+    pub fn tower_level(&mut self, coordinate: &Coordinate) -> Option<usize> {
+        self.app
+            .world
+            .query::<&Tower>()
+            .iter(&self.app.world)
+            .find(|tower| &tower.coordinate == coordinate)
+            .map(|tower| tower.level)
+    }
+
+    /// Fill all open slots of the puzzle at the given coordinate, as if the player placed the pieces
+    pub fn fill_puzzle(&mut self, coordinate: &Coordinate) {
+        let Some(puzzle_id) = self
+            .app
+            .world
+            .resource::<Puzzles>()
+            .towers
+            .iter()
+            .find(|puzzle| &puzzle.coordinate == coordinate)
+            .map(|puzzle| puzzle.id)
+        else {
+            return;
+        };
+        let open_slots: Vec<Entity> = self
+            .app
+            .world
+            .query::<(Entity, &PuzzleSlot)>()
+            .iter(&self.app.world)
+            .filter(|(_, slot)| slot.puzzle_id == puzzle_id && !slot.filled)
+            .map(|(entity, _)| entity)
+            .collect();
+        for slot in open_slots {
+            self.app.world.entity_mut(slot).insert(ToFill);
+        }
+    }
+}
+
+fn stub_textures() -> TextureAssets {
+    TextureAssets {
+        blank: Handle::default(),
+        tower_plot: Handle::default(),
+        tower: Handle::default(),
+        path: Handle::default(),
+        castle: Handle::default(),
+        cloud: Handle::default(),
+        spawn: Handle::default(),
+    }
+}
@@ -0,0 +1,192 @@
+use crate::enemies::{Enemy, EnemySet, Tameable};
+use crate::map::Map;
+use crate::AppState;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::ops::{Deref, DerefMut};
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EnemyGrid>()
+            .init_resource::<TameableGrid>()
+            .add_systems(
+                Update,
+                update_enemy_grids
+                    .after(EnemySet::Move)
+                    .before(EnemySet::Damage)
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+/// Buckets entity positions into square cells, so lookups only need to check nearby cells
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(Entity, Vec2)>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::default(),
+        }
+    }
+
+    /// Remove all entries. Cells are kept to reuse their allocations.
+    pub fn clear(&mut self, cell_size: f32) {
+        if self.cell_size != cell_size {
+            self.cells.clear();
+            self.cell_size = cell_size;
+        }
+        for entries in self.cells.values_mut() {
+            entries.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push((entity, position));
+    }
+
+    /// All entries within `radius` around `center`
+    pub fn query_radius(
+        &self,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let (min_x, min_y) = self.cell(center - Vec2::splat(radius));
+        let (max_x, max_y) = self.cell(center + Vec2::splat(radius));
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(move |(_, position)| position.distance_squared(center) <= radius * radius)
+    }
+
+    pub fn closest(&self, center: Vec2, radius: f32) -> Option<(Entity, f32)> {
+        self.query_radius(center, radius)
+            .map(|(entity, position)| (entity, position.distance(center)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    fn cell(&self, position: Vec2) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+/// Enemies that are still on their way to the castle
+#[derive(Resource)]
+pub struct EnemyGrid(SpatialGrid);
+
+/// Beaten enemies that can be picked up
+#[derive(Resource)]
+pub struct TameableGrid(SpatialGrid);
+
+impl Default for EnemyGrid {
+    fn default() -> Self {
+        EnemyGrid(SpatialGrid::new(64.))
+    }
+}
+
+impl Default for TameableGrid {
+    fn default() -> Self {
+        TameableGrid(SpatialGrid::new(64.))
+    }
+}
+
+impl Deref for EnemyGrid {
+    type Target = SpatialGrid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for EnemyGrid {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Deref for TameableGrid {
+    type Target = SpatialGrid;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for TameableGrid {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+pub(crate) fn update_enemy_grids(
+    map: Res<Map>,
+    mut enemy_grid: ResMut<EnemyGrid>,
+    mut tameable_grid: ResMut<TameableGrid>,
+    enemy_query: Query<(Entity, &Transform, Option<&Tameable>), With<Enemy>>,
+) {
+    enemy_grid.clear(map.tile_size);
+    tameable_grid.clear(map.tile_size);
+    for (entity, transform, tameable) in enemy_query.iter() {
+        let position = transform.translation.truncate();
+        if tameable.is_some() {
+            tameable_grid.insert(entity, position);
+        } else {
+            enemy_grid.insert(entity, position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_entries_in_neighbouring_cells() {
+        let mut grid = SpatialGrid::new(64.);
+        grid.insert(Entity::from_raw(0), Vec2::new(60., 60.));
+        grid.insert(Entity::from_raw(1), Vec2::new(70., 70.));
+        grid.insert(Entity::from_raw(2), Vec2::new(300., 300.));
+        let found: Vec<Entity> = grid
+            .query_radius(Vec2::new(65., 65.), 20.)
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(found.len(), 2);
+        assert!(!found.contains(&Entity::from_raw(2)));
+    }
+
+    #[test]
+    fn ignores_entries_outside_of_radius_in_overlapping_cells() {
+        let mut grid = SpatialGrid::new(64.);
+        grid.insert(Entity::from_raw(0), Vec2::new(1., 1.));
+        assert_eq!(grid.query_radius(Vec2::new(60., 60.), 10.).count(), 0);
+    }
+
+    #[test]
+    fn closest_picks_nearest_entry() {
+        let mut grid = SpatialGrid::new(64.);
+        grid.insert(Entity::from_raw(0), Vec2::new(-10., 0.));
+        grid.insert(Entity::from_raw(1), Vec2::new(4., 0.));
+        let (entity, distance) = grid.closest(Vec2::ZERO, 16.).unwrap();
+        assert_eq!(entity, Entity::from_raw(1));
+        assert!((distance - 4.).abs() < 0.001);
+    }
+
+    #[test]
+    fn clear_removes_all_entries() {
+        let mut grid = SpatialGrid::new(64.);
+        grid.insert(Entity::from_raw(0), Vec2::new(10., 10.));
+        grid.clear(32.);
+        assert!(grid.closest(Vec2::new(10., 10.), 16.).is_none());
+    }
+}
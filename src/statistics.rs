@@ -0,0 +1,28 @@
+use crate::AppState;
+use bevy::prelude::*;
+
+pub struct StatisticsPlugin;
+
+impl Plugin for StatisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStatistics>()
+            .add_systems(OnEnter(AppState::InGame), reset_statistics);
+    }
+}
+
+/// Numbers collected over a single run. Reset whenever a new game starts.
+#[derive(Default, Resource)]
+pub struct RunStatistics {
+    pub rerolls: usize,
+    pub gold_spent_on_rerolls: usize,
+    pub pieces_spent_on_rerolls: usize,
+    pub gold_earned: usize,
+    pub gold_spent_on_repairs: usize,
+    pub waves_cleared: usize,
+    pub towers_sold: usize,
+    pub gold_refunded: usize,
+}
+
+fn reset_statistics(mut statistics: ResMut<RunStatistics>) {
+    *statistics = RunStatistics::default();
+}
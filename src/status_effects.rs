@@ -0,0 +1,215 @@
+use crate::enemies::{DamageEnemy, EnemySet, Tameable};
+use crate::AppState;
+use bevy::prelude::*;
+
+pub struct StatusEffectsPlugin;
+
+impl Plugin for StatusEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_status_effects
+                .in_set(EnemySet::Damage)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatusEffectKind {
+    /// Multiplies the movement speed with the given factor
+    Slow(f32),
+    /// Damage per tick. Several poisons stack.
+    Poison(i32),
+    /// Damage per tick
+    Burn(i32),
+    Stun,
+}
+
+/// What happens if an effect of the same kind is applied to an enemy that already has one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stacking {
+    /// Replace the running effect and start its duration over
+    Refresh,
+    /// Add another instance, up to the given maximum
+    Stack(usize),
+    /// Keep the running effect and drop the new one
+    Ignore,
+}
+
+#[derive(Clone, Debug)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    duration: Timer,
+    tick: Option<Timer>,
+}
+
+impl StatusEffect {
+    pub fn slow(factor: f32, seconds: f32) -> Self {
+        StatusEffect {
+            kind: StatusEffectKind::Slow(factor),
+            duration: Timer::from_seconds(seconds, TimerMode::Once),
+            tick: None,
+        }
+    }
+
+    pub fn poison(damage: i32, seconds: f32) -> Self {
+        StatusEffect {
+            kind: StatusEffectKind::Poison(damage),
+            duration: Timer::from_seconds(seconds, TimerMode::Once),
+            tick: Some(Timer::from_seconds(0.5, TimerMode::Repeating)),
+        }
+    }
+
+    pub fn burn(damage: i32, seconds: f32) -> Self {
+        StatusEffect {
+            kind: StatusEffectKind::Burn(damage),
+            duration: Timer::from_seconds(seconds, TimerMode::Once),
+            tick: Some(Timer::from_seconds(0.25, TimerMode::Repeating)),
+        }
+    }
+
+    pub fn stun(seconds: f32) -> Self {
+        StatusEffect {
+            kind: StatusEffectKind::Stun,
+            duration: Timer::from_seconds(seconds, TimerMode::Once),
+            tick: None,
+        }
+    }
+
+    pub fn stacking(&self) -> Stacking {
+        match self.kind {
+            StatusEffectKind::Slow(_) | StatusEffectKind::Burn(_) => Stacking::Refresh,
+            StatusEffectKind::Poison(_) => Stacking::Stack(5),
+            StatusEffectKind::Stun => Stacking::Ignore,
+        }
+    }
+
+    fn same_kind(&self, other: &StatusEffect) -> bool {
+        std::mem::discriminant(&self.kind) == std::mem::discriminant(&other.kind)
+    }
+}
+
+/// All effects currently running on an enemy
+#[derive(Component, Default)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn apply(&mut self, effect: StatusEffect) {
+        let running = self
+            .effects
+            .iter()
+            .filter(|running| running.same_kind(&effect))
+            .count();
+        match effect.stacking() {
+            Stacking::Refresh => {
+                self.effects.retain(|running| !running.same_kind(&effect));
+                self.effects.push(effect);
+            }
+            Stacking::Stack(max) => {
+                if running < max {
+                    self.effects.push(effect);
+                }
+            }
+            Stacking::Ignore => {
+                if running == 0 {
+                    self.effects.push(effect);
+                }
+            }
+        }
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.effects
+            .iter()
+            .fold(1., |multiplier, effect| match effect.kind {
+                StatusEffectKind::Slow(factor) => multiplier * factor,
+                StatusEffectKind::Stun => 0.,
+                _ => multiplier,
+            })
+    }
+}
+
+fn update_status_effects(
+    time: Res<Time>,
+    mut enemy_query: Query<(Entity, &mut StatusEffects), Without<Tameable>>,
+    mut damage_enemy: EventWriter<DamageEnemy>,
+) {
+    for (entity, mut status_effects) in enemy_query.iter_mut() {
+        if status_effects.effects.is_empty() {
+            continue;
+        }
+        for effect in status_effects.effects.iter_mut() {
+            effect.duration.tick(time.delta());
+            let Some(tick) = effect.tick.as_mut() else {
+                continue;
+            };
+            let ticks = tick.tick(time.delta()).times_finished_this_tick() as i32;
+            let damage = match effect.kind {
+                StatusEffectKind::Poison(damage) | StatusEffectKind::Burn(damage) => damage,
+                _ => 0,
+            };
+            if ticks > 0 && damage > 0 {
+                damage_enemy.send(DamageEnemy {
+                    target: entity,
+                    amount: ticks * damage,
+                    color: None,
+                });
+            }
+        }
+        status_effects
+            .effects
+            .retain(|effect| !effect.duration.finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn tick(effects: &mut StatusEffects, seconds: f32) {
+        for effect in effects.effects.iter_mut() {
+            effect.duration.tick(Duration::from_secs_f32(seconds));
+        }
+        effects.effects.retain(|effect| !effect.duration.finished());
+    }
+
+    #[test]
+    fn slow_refreshes_its_duration() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::slow(0.5, 1.));
+        tick(&mut effects, 0.6);
+        effects.apply(StatusEffect::slow(0.8, 1.));
+        assert_eq!(effects.effects.len(), 1);
+        assert_eq!(effects.effects[0].duration.elapsed_secs(), 0.);
+        assert_eq!(effects.speed_multiplier(), 0.8);
+    }
+
+    #[test]
+    fn poison_stacks_up_to_five_times() {
+        let mut effects = StatusEffects::default();
+        for _ in 0..7 {
+            effects.apply(StatusEffect::poison(2, 3.));
+        }
+        assert_eq!(effects.effects.len(), 5);
+    }
+
+    #[test]
+    fn stun_is_ignored_while_active() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffect::stun(0.2));
+        tick(&mut effects, 0.1);
+        effects.apply(StatusEffect::stun(0.2));
+        assert_eq!(effects.effects.len(), 1);
+        assert!(effects.effects[0].duration.elapsed_secs() > 0.);
+        assert_eq!(effects.speed_multiplier(), 0.);
+
+        tick(&mut effects, 0.2);
+        assert_eq!(effects.speed_multiplier(), 1.);
+        effects.apply(StatusEffect::stun(0.2));
+        assert_eq!(effects.effects.len(), 1);
+    }
+}
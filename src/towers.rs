@@ -1,39 +1,198 @@
-use crate::bullets::{spawn_bullet, Bullet};
-use crate::enemies::{Enemy, Tameable};
+use crate::actions::Action;
+use crate::bullets::{spawn_bullet, Bullet, ProjectileKind};
+use crate::enemies::{Enemy, EnemyColor, Tameable};
 use crate::loading::TextureAssets;
 use crate::map::{Coordinate, Map, MapTile, Tile};
-use crate::puzzle::CompletePuzzle;
+use crate::puzzle::{CompletePuzzle, PickSource};
+use crate::settings::Settings;
+use crate::spatial::EnemyGrid;
+use crate::statistics::RunStatistics;
+use crate::status_effects::StatusEffect;
+use crate::ui::GameState;
 use crate::{AppState, TOWER_Z};
 use bevy::prelude::*;
+use serde::Deserialize;
 use std::ops::{Deref, DerefMut};
 
+/// Gold a tower is worth per level
+const TOWER_LEVEL_VALUE: usize = 20;
+const SELL_REFUND_PERCENT: usize = 50;
+/// Distance from the tower center in which a right click sells the tower
+const SELL_RADIUS: f32 = 10.;
+const FIRE_DAMAGE: i32 = 10;
+const SNIPER_RANGE: f32 = 60.;
+const HEAVY_DAMAGE: i32 = 20;
+/// Heavy bullets are slower than the tower's own speed
+pub(crate) const HEAVY_SPEED_PENALTY: f32 = 40.;
+/// Heavy bullets hit an area, so they come slower
+const HEAVY_RELOAD_FACTOR: f32 = 2.;
+const RAPID_RELOAD_FACTOR: f32 = 0.6;
+
 pub struct TowersPlugin;
 
 impl Plugin for TowersPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<TowerShot>()
+        app.init_resource::<TowerDefinition>()
+            .add_event::<TowerShot>()
+            .add_event::<SellTower>()
+            .add_event::<TowerBuilt>()
             .add_systems(OnEnter(AppState::InGame), spawn_map_tower)
             .add_systems(
                 Update,
-                (shoot, build_and_upgrade_towers).run_if(in_state(AppState::InGame)),
+                (
+                    shoot,
+                    build_and_upgrade_towers,
+                    (sell_input, sell_towers).chain(),
+                )
+                    .run_if(in_state(AppState::InGame)),
             )
             .add_systems(OnExit(AppState::InGame), break_down_towers);
     }
 }
 
 #[derive(Event)]
-pub struct TowerShot;
+pub struct TowerShot {
+    pub tower: Entity,
+    pub position: Vec2,
+}
+
+/// A completed puzzle built a new tower or upgraded an existing one
+#[derive(Event)]
+pub struct TowerBuilt {
+    pub upgrade: bool,
+}
+
+#[derive(Event)]
+pub struct SellTower {
+    pub coordinate: Coordinate,
+}
+
+/// Stats of new towers and what every upgrade adds. Mods can replace the built-in values.
+#[derive(Clone, Debug, Resource, Deserialize)]
+#[serde(default)]
+pub struct TowerDefinition {
+    pub range: f32,
+    pub damage: i32,
+    /// Bullet speed
+    pub speed: f32,
+    pub upgrade_range: f32,
+    pub upgrade_damage: i32,
+    pub upgrade_speed: f32,
+}
+
+impl Default for TowerDefinition {
+    fn default() -> Self {
+        TowerDefinition {
+            range: 100.,
+            damage: 15,
+            speed: 200.,
+            upgrade_range: 5.,
+            upgrade_damage: 5,
+            upgrade_speed: 20.,
+        }
+    }
+}
 
 #[derive(Component)]
-struct Tower {
-    level: usize,
+pub(crate) struct Tower {
+    pub(crate) level: usize,
+    /// Stats from the definition and upgrades, without the specialization bonus
     range: f32,
     damage: i32,
     speed: f32,
-    coordinate: Coordinate,
+    pub(crate) coordinate: Coordinate,
+    specialization: Option<Specialization>,
+    /// Applied to enemies hit by this tower
+    effects: Vec<StatusEffect>,
+    /// Inherited from the pieces of the last completed puzzle
+    color: Option<EnemyColor>,
+}
+
+impl Tower {
+    fn refund(&self) -> usize {
+        self.level * TOWER_LEVEL_VALUE * SELL_REFUND_PERCENT / 100
+    }
+
+    fn projectile(&self) -> ProjectileKind {
+        match self.specialization {
+            Some(Specialization::Sniper) => ProjectileKind::Predictive,
+            Some(Specialization::Heavy) => ProjectileKind::Burst { radius: 40. },
+            _ => ProjectileKind::Homing,
+        }
+    }
+
+    fn cooldown(&self) -> TowerCooldown {
+        TowerCooldown(Timer::from_seconds(self.reload(), TimerMode::Repeating))
+    }
+
+    /// Seconds between two shots
+    fn reload(&self) -> f32 {
+        let seconds = match self.level {
+            1 => 0.3,
+            2 => 0.2,
+            _ => 0.1,
+        };
+        match self.specialization {
+            Some(Specialization::Rapid) => seconds * RAPID_RELOAD_FACTOR,
+            Some(Specialization::Heavy) => seconds * HEAVY_RELOAD_FACTOR,
+            _ => seconds,
+        }
+    }
+
+    fn range(&self) -> f32 {
+        match self.specialization {
+            Some(Specialization::Sniper) => self.range + SNIPER_RANGE,
+            _ => self.range,
+        }
+    }
+
+    fn damage(&self) -> i32 {
+        match self.specialization {
+            Some(Specialization::Fire) => self.damage + FIRE_DAMAGE,
+            Some(Specialization::Heavy) => self.damage + HEAVY_DAMAGE,
+            _ => self.damage,
+        }
+    }
+
+    fn speed(&self) -> f32 {
+        match self.specialization {
+            Some(Specialization::Heavy) => self.speed - HEAVY_SPEED_PENALTY,
+            _ => self.speed,
+        }
+    }
+
+    fn upgrade(&mut self, definition: &TowerDefinition, specialization: Option<Specialization>) {
+        self.level += 1;
+        self.speed += definition.upgrade_speed;
+        self.damage += definition.upgrade_damage;
+        self.range += definition.upgrade_range;
+        if let Some(specialization) = specialization {
+            self.specialize(specialization);
+        }
+    }
+
+    /// The bonus stats follow from the specialization, so specializing again does not stack them
+    fn specialize(&mut self, specialization: Specialization) {
+        self.effects = vec![match specialization {
+            Specialization::Fire => StatusEffect::burn(3, 2.),
+            Specialization::Sniper => StatusEffect::poison(2, 3.),
+            Specialization::Rapid => StatusEffect::slow(0.8, 1.),
+            Specialization::Heavy => StatusEffect::stun(0.2),
+        }];
+        self.specialization = Some(specialization);
+    }
 }
 
-fn spawn_map_tower(mut commands: Commands, map: Res<Map>) {
+/// Bonus a tower gets from completing a puzzle that matches a [`Recipe`](crate::recipes::Recipe)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Specialization {
+    Fire,
+    Sniper,
+    Rapid,
+    Heavy,
+}
+
+fn spawn_map_tower(mut commands: Commands, map: Res<Map>, definition: Res<TowerDefinition>) {
     let mut tower_positions: Vec<Coordinate> = vec![];
 
     for (row_index, row) in map.tiles.iter().enumerate() {
@@ -48,86 +207,91 @@ fn spawn_map_tower(mut commands: Commands, map: Res<Map>) {
     }
 
     for coordinate in tower_positions {
-        commands.spawn(TowerBundle::new(coordinate));
+        commands.spawn(TowerBundle::new(coordinate, &definition));
     }
 }
 
-fn shoot(
+pub(crate) fn shoot(
     mut commands: Commands,
     time: Res<Time>,
-    mut tower_query: Query<(&Transform, &Tower, &mut TowerCooldown)>,
+    mut tower_query: Query<(Entity, &Transform, &Tower, &mut TowerCooldown)>,
     mut tower_shot: EventWriter<TowerShot>,
-    mut enemies_query: Query<(Entity, &Transform, &mut Enemy), Without<Tameable>>,
+    enemy_grid: Res<EnemyGrid>,
+    enemies_query: Query<(Entity, &Transform, &Enemy), Without<Tameable>>,
 ) {
-    for (tower_pos, tower, mut tower_cooldown) in tower_query.iter_mut() {
+    for (tower_entity, tower_pos, tower, mut tower_cooldown) in tower_query.iter_mut() {
         tower_cooldown.tick(time.delta());
         if tower_cooldown.just_finished() {
-            let furthest_target: Option<(Entity, f32)> = enemies_query
-                .iter_mut()
-                .filter(|(_, pos, _)| {
-                    let distance = pos.translation - tower_pos.translation;
-                    distance.length() < tower.range
-                })
-                .fold(None, |acc, (entity, _, enemy)| {
-                    if let Some((_, old_travelled)) = acc {
-                        if enemy.travelled > old_travelled {
-                            Some((entity, enemy.travelled))
-                        } else {
-                            acc
-                        }
-                    } else {
-                        Some((entity, enemy.travelled))
-                    }
-                });
+            let furthest_target: Option<(Entity, f32)> = enemy_grid
+                .query_radius(tower_pos.translation.truncate(), tower.range())
+                .filter_map(|(entity, _)| enemies_query.get(entity).ok())
+                .map(|(entity, _, enemy)| (entity, enemy.travelled))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b));
 
             if let Some((target, _)) = furthest_target {
-                let (_, _, mut enemy) = enemies_query.get_mut(target).unwrap();
-                let bullet = Bullet {
-                    damage: tower.damage,
-                    speed: tower.speed,
-                };
-                enemy.bullets.push({
-                    let mut translation = tower_pos.translation;
-                    translation.z += 2.;
-                    spawn_bullet(&mut commands, bullet, translation)
+                let (_, target_pos, enemy) = enemies_query.get(target).unwrap();
+                let mut bullet = Bullet::new(tower.projectile(), tower.damage(), tower.speed());
+                bullet.color = tower.color.clone();
+                bullet.effects = tower.effects.clone();
+                bullet.aim(
+                    tower_pos.translation.truncate(),
+                    target,
+                    target_pos.translation.truncate(),
+                    enemy.velocity,
+                );
+                let mut translation = tower_pos.translation;
+                translation.z += 2.;
+                spawn_bullet(&mut commands, bullet, translation);
+                tower_shot.send(TowerShot {
+                    tower: tower_entity,
+                    position: tower_pos.translation.truncate(),
                 });
-                tower_shot.send(TowerShot);
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_and_upgrade_towers(
     mut commands: Commands,
     mut event_reader: EventReader<CompletePuzzle>,
     texture_assets: Res<TextureAssets>,
+    settings: Res<Settings>,
+    definition: Res<TowerDefinition>,
+    mut tower_built: EventWriter<TowerBuilt>,
     mut tower_query: Query<(&mut Tower, &mut TowerCooldown)>,
-    mut map_tiles_query: Query<(&Transform, &mut Handle<Image>), With<MapTile>>,
+    mut map_tiles_query: Query<(&Transform, &mut Handle<Image>, &mut Sprite), With<MapTile>>,
 ) {
     for completed_puzzle in event_reader.iter() {
         let coordinate: Coordinate = completed_puzzle.coordinate.clone();
+        let specialization = completed_puzzle
+            .recipe
+            .map(|recipe| recipe.specialization());
+        for (transform, mut image, mut sprite) in map_tiles_query.iter_mut() {
+            if transform.translation.x == coordinate.x && transform.translation.y == coordinate.y {
+                *image = texture_assets.tower.clone();
+                sprite.color =
+                    Color::WHITE * 0.5 + completed_puzzle.color.to_color(settings.palette) * 0.5;
+            }
+        }
         if let Some((mut tower, mut tower_cooldown)) = tower_query
             .iter_mut()
             .find(|(tower, _)| tower.coordinate == coordinate)
         {
-            tower.level += 1;
-            tower.speed += 20.;
-            tower.damage += 5;
-            tower.range += 5.;
-
-            *tower_cooldown = TowerCooldown(Timer::from_seconds(
-                if tower.level == 2 { 0.2 } else { 0.1 },
-                TimerMode::Repeating,
-            ));
+            tower.upgrade(&definition, specialization);
+            tower.color = Some(completed_puzzle.color.clone());
+
+            *tower_cooldown = tower.cooldown();
+            tower_built.send(TowerBuilt { upgrade: true });
         } else {
-            for (transform, mut image) in map_tiles_query.iter_mut() {
-                if transform.translation.x == coordinate.x
-                    && transform.translation.y == coordinate.y
-                {
-                    *image = texture_assets.tower.clone()
-                }
+            let mut tower_bundle = TowerBundle::new(coordinate, &definition);
+            tower_bundle.tower.color = Some(completed_puzzle.color.clone());
+            if let Some(specialization) = specialization {
+                tower_bundle.tower.specialize(specialization);
+                tower_bundle.cooldown = tower_bundle.tower.cooldown();
             }
-            commands.spawn(TowerBundle::new(coordinate));
+            commands.spawn(tower_bundle);
+            tower_built.send(TowerBuilt { upgrade: false });
         }
     }
 }
@@ -163,14 +327,17 @@ pub struct TowerBundle {
 }
 
 impl TowerBundle {
-    fn new(coordinate: Coordinate) -> Self {
+    pub(crate) fn new(coordinate: Coordinate, definition: &TowerDefinition) -> Self {
         TowerBundle {
             tower: Tower {
-                range: 100.,
-                damage: 15,
+                range: definition.range,
+                damage: definition.damage,
                 level: 1,
-                speed: 200.,
+                speed: definition.speed,
                 coordinate: coordinate.clone(),
+                specialization: None,
+                effects: vec![],
+                color: None,
             },
             transform: Transform::from_translation(coordinate.to_translation(TOWER_Z)),
             cooldown: TowerCooldown::default(),
@@ -178,22 +345,113 @@ impl TowerBundle {
     }
 }
 
-fn break_down_towers(
+fn sell_input(
+    actions: Res<Input<Action>>,
+    pick_source: Res<PickSource>,
+    tower_query: Query<&Tower>,
+    mut sell_tower: EventWriter<SellTower>,
+) {
+    if !actions.just_pressed(Action::Sell) {
+        return;
+    }
+    let cursor_position = pick_source.last_cursor_pos;
+    if let Some(tower) = tower_query.iter().find(|tower| {
+        Vec2::new(
+            tower.coordinate.x - cursor_position.x,
+            tower.coordinate.y - cursor_position.y,
+        )
+        .length()
+            < SELL_RADIUS
+    }) {
+        sell_tower.send(SellTower {
+            coordinate: tower.coordinate.clone(),
+        });
+    }
+}
+
+fn sell_towers(
     mut commands: Commands,
-    tower_query: Query<(Entity, &Tower)>,
-    mut map_tiles_query: Query<(&Transform, &mut Handle<ColorMaterial>, &MapTile)>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut sell_reader: EventReader<SellTower>,
+    mut game_state: ResMut<GameState>,
+    mut statistics: ResMut<RunStatistics>,
     texture_assets: Res<TextureAssets>,
+    tower_query: Query<(Entity, &Tower)>,
+    mut map_tiles_query: Query<(&Transform, &mut Handle<Image>, &mut Sprite), With<MapTile>>,
 ) {
-    for (entity, tower) in tower_query.iter() {
-        for (transform, mut material, map_tile) in map_tiles_query.iter_mut() {
+    for sold_tower in sell_reader.iter() {
+        let Some((entity, tower)) = tower_query
+            .iter()
+            .find(|(_, tower)| tower.coordinate == sold_tower.coordinate)
+        else {
+            continue;
+        };
+        if game_state.health > 0 {
+            game_state.gold += tower.refund();
+            statistics.gold_refunded += tower.refund();
+        }
+        statistics.towers_sold += 1;
+        for (transform, mut image, mut sprite) in map_tiles_query.iter_mut() {
             if transform.translation.x == tower.coordinate.x
                 && transform.translation.y == tower.coordinate.y
-                && map_tile.tile != Tile::Tower
             {
-                *material = materials.add(texture_assets.tower_plot.clone().into())
+                *image = texture_assets.tower_plot.clone();
+                sprite.color = Color::WHITE;
             }
         }
         commands.entity(entity).despawn();
     }
 }
+
+fn break_down_towers(
+    mut commands: Commands,
+    tower_query: Query<Entity, With<Tower>>,
+    mut map_tiles_query: Query<(&MapTile, &mut Handle<Image>, &mut Sprite)>,
+    texture_assets: Res<TextureAssets>,
+) {
+    for entity in tower_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    // towers might have been built, sold or tinted on any plot
+    for (map_tile, mut image, mut sprite) in map_tiles_query.iter_mut() {
+        if map_tile.tile == Tile::Tower || map_tile.tile == Tile::TowerPlot {
+            *image = texture_assets.get_handle_for_tile(&map_tile.tile);
+            sprite.color = Color::WHITE;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specialization_bonus_does_not_stack() {
+        let definition = TowerDefinition::default();
+        let mut tower = TowerBundle::new(Coordinate::default(), &definition).tower;
+        for _ in 0..10 {
+            tower.upgrade(&definition, Some(Specialization::Heavy));
+            assert!(tower.speed() > 0.);
+        }
+        assert_eq!(
+            tower.speed(),
+            definition.speed + 10. * definition.upgrade_speed - HEAVY_SPEED_PENALTY
+        );
+        assert_eq!(
+            tower.damage(),
+            definition.damage + 10 * definition.upgrade_damage + HEAVY_DAMAGE
+        );
+        assert_eq!(tower.reload(), 0.1 * HEAVY_RELOAD_FACTOR);
+        assert_eq!(tower.effects.len(), 1);
+
+        tower.upgrade(&definition, Some(Specialization::Sniper));
+        assert_eq!(
+            tower.range(),
+            definition.range + 11. * definition.upgrade_range + SNIPER_RANGE
+        );
+        assert_eq!(
+            tower.damage(),
+            definition.damage + 11 * definition.upgrade_damage
+        );
+        assert_eq!(tower.reload(), 0.1);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::statistics::RunStatistics;
 use crate::AppState;
 use bevy::prelude::*;
 
@@ -8,14 +9,31 @@ impl Plugin for UiPlugin {
         app.insert_resource(GameState::default())
             .init_resource::<ButtonColors>()
             .add_systems(OnEnter(AppState::InGame), init_life)
+            .add_systems(OnExit(AppState::InGame), break_down_hud)
             .add_systems(
                 Update,
-                (update_game_state, retry_system, click_retry_button)
+                (
+                    update_game_state,
+                    retry_system,
+                    click_retry_button,
+                    click_repair_button,
+                )
                     .run_if(in_state(AppState::InGame)),
             );
     }
 }
 
+/// Buttons of one kind whose interaction changed since the last frame
+pub type ButtonInteractions<'w, 's, B> = Query<
+    'w,
+    's,
+    (&'static Interaction, &'static mut BackgroundColor),
+    (Changed<Interaction>, With<B>),
+>;
+
+/// One of the HUD counters, kept apart from the other two so all can be changed at once
+type Counter<T, A, B> = (With<T>, Without<A>, Without<B>);
+
 #[derive(Resource)]
 pub struct ButtonColors {
     pub normal: Color,
@@ -40,10 +58,23 @@ struct HealthText;
 #[derive(Component)]
 struct ScoreText;
 
+#[derive(Component)]
+struct GoldText;
+
+#[derive(Component)]
+struct RepairButton;
+
+#[derive(Component)]
+struct Hud;
+
+/// Gold needed to give the castle one more health
+pub const REPAIR_COST: usize = 25;
+
 #[derive(Resource)]
 pub struct GameState {
     pub health: usize,
     pub score: usize,
+    pub gold: usize,
     pub enemy_health: i32,
 }
 
@@ -52,6 +83,7 @@ impl Default for GameState {
         GameState {
             health: 20,
             score: 0,
+            gold: 0,
             enemy_health: 1,
         }
     }
@@ -61,6 +93,7 @@ fn init_life(
     mut commands: Commands,
     asset_server: ResMut<AssetServer>,
     game_state: Res<GameState>,
+    button_colors: Res<ButtonColors>,
 ) {
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     // root node
@@ -70,11 +103,13 @@ fn init_life(
                 position_type: PositionType::Absolute,
                 left: Val::Px(10.),
                 top: Val::Px(10.),
+                flex_direction: FlexDirection::Column,
                 ..Default::default()
             },
             background_color: Color::NONE.into(),
             ..Default::default()
         })
+        .insert(Hud)
         .with_children(|parent| {
             parent
                 .spawn(TextBundle {
@@ -83,12 +118,50 @@ fn init_life(
                         TextStyle {
                             font_size: 40.0,
                             color: Color::rgb(0.6, 0.6, 0.6),
-                            font,
+                            font: font.clone(),
                         },
                     ),
                     ..Default::default()
                 })
                 .insert(HealthText);
+            parent
+                .spawn(TextBundle {
+                    text: Text::from_section(
+                        format!("Gold: {}", game_state.gold),
+                        TextStyle {
+                            font_size: 30.0,
+                            color: Color::rgb(0.8, 0.7, 0.3),
+                            font: font.clone(),
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(GoldText);
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        padding: UiRect::all(Val::Px(5.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    background_color: button_colors.normal.into(),
+                    ..Default::default()
+                })
+                .insert(RepairButton)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle {
+                        text: Text::from_section(
+                            format!("+1 Health ({} Gold)", REPAIR_COST),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                                font,
+                            },
+                        ),
+                        ..Default::default()
+                    });
+                });
         });
     let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     commands
@@ -102,6 +175,7 @@ fn init_life(
             background_color: Color::NONE.into(),
             ..Default::default()
         })
+        .insert(Hud)
         .with_children(|parent| {
             parent
                 .spawn(TextBundle {
@@ -124,8 +198,9 @@ fn init_life(
 
 fn update_game_state(
     game_state: Res<GameState>,
-    mut health_query: Query<&mut Text, (With<HealthText>, Without<ScoreText>)>,
-    mut score_query: Query<&mut Text, (With<ScoreText>, Without<HealthText>)>,
+    mut health_query: Query<&mut Text, Counter<HealthText, ScoreText, GoldText>>,
+    mut score_query: Query<&mut Text, Counter<ScoreText, HealthText, GoldText>>,
+    mut gold_query: Query<&mut Text, Counter<GoldText, HealthText, ScoreText>>,
 ) {
     if game_state.is_changed() {
         for mut text in health_query.iter_mut() {
@@ -134,6 +209,9 @@ fn update_game_state(
         for mut text in score_query.iter_mut() {
             text.sections.first_mut().unwrap().value = format!("Score: {}", game_state.score);
         }
+        for mut text in gold_query.iter_mut() {
+            text.sections.first_mut().unwrap().value = format!("Gold: {}", game_state.gold);
+        }
     }
 }
 
@@ -181,7 +259,7 @@ fn click_retry_button(
     mut game_state: ResMut<GameState>,
     mut interaction_query: Query<
         (Entity, &Interaction, &mut BackgroundColor, &Children),
-        With<Button>,
+        With<RetryButton>,
     >,
     text_query: Query<Entity, With<Text>>,
 ) {
@@ -203,3 +281,39 @@ fn click_retry_button(
         }
     }
 }
+
+fn click_repair_button(
+    button_colors: Res<ButtonColors>,
+    mut game_state: ResMut<GameState>,
+    mut statistics: ResMut<RunStatistics>,
+    mut interaction_query: ButtonInteractions<RepairButton>,
+) {
+    for (interaction, mut color) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Pressed => {
+                if game_state.health > 0 && game_state.gold >= REPAIR_COST {
+                    game_state.gold -= REPAIR_COST;
+                    game_state.health += 1;
+                    statistics.gold_spent_on_repairs += REPAIR_COST;
+                }
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_colors.normal.into();
+            }
+        }
+    }
+}
+
+/// Also removes the retry button, the game can be left from the game over screen while play-testing
+fn break_down_hud(
+    mut commands: Commands,
+    hud_query: Query<Entity, With<Hud>>,
+    retry_query: Query<Entity, With<RetryButton>>,
+) {
+    for entity in hud_query.iter().chain(retry_query.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+}
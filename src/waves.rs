@@ -0,0 +1,146 @@
+use crate::abilities::EnemyAbility;
+use crate::boss::{BossDefinition, BossPhase, PhaseBehaviour};
+use crate::enemies::EnemyColor;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// All waves of a run. The last wave, ending with a boss, repeats for the rest of the game.
+/// Without any waves, no enemies spawn.
+#[derive(Clone, Resource, Deserialize)]
+#[serde(transparent)]
+pub struct Waves {
+    pub waves: Vec<WaveDefinition>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct WaveDefinition {
+    pub enemies: usize,
+    #[serde(default)]
+    pub abilities: Vec<AbilityChance>,
+    /// Spawned after all other enemies of the wave
+    #[serde(default)]
+    pub boss: Option<BossDefinition>,
+}
+
+/// Chance for every enemy of a wave to spawn with the given ability
+#[derive(Clone, Deserialize)]
+pub struct AbilityChance {
+    pub ability: EnemyAbility,
+    pub chance: f64,
+}
+
+impl Waves {
+    pub fn get(&self, index: usize) -> Option<&WaveDefinition> {
+        self.waves.get(self.definition_index(index))
+    }
+
+    /// Index of the definition a wave follows, the last one repeats
+    pub fn definition_index(&self, index: usize) -> usize {
+        index.min(self.waves.len().saturating_sub(1))
+    }
+}
+
+impl Default for Waves {
+    fn default() -> Self {
+        Waves {
+            waves: vec![
+                WaveDefinition {
+                    enemies: 10,
+                    abilities: vec![],
+                    boss: None,
+                },
+                WaveDefinition {
+                    enemies: 10,
+                    abilities: vec![AbilityChance {
+                        ability: EnemyAbility::Sprinter,
+                        chance: 0.3,
+                    }],
+                    boss: None,
+                },
+                WaveDefinition {
+                    enemies: 15,
+                    abilities: vec![
+                        AbilityChance {
+                            ability: EnemyAbility::Shielded,
+                            chance: 0.3,
+                        },
+                        AbilityChance {
+                            ability: EnemyAbility::Sprinter,
+                            chance: 0.2,
+                        },
+                    ],
+                    boss: None,
+                },
+                WaveDefinition {
+                    enemies: 15,
+                    abilities: vec![
+                        AbilityChance {
+                            ability: EnemyAbility::Healer,
+                            chance: 0.2,
+                        },
+                        AbilityChance {
+                            ability: EnemyAbility::Splitting,
+                            chance: 0.2,
+                        },
+                    ],
+                    boss: None,
+                },
+                WaveDefinition {
+                    enemies: 20,
+                    abilities: vec![
+                        AbilityChance {
+                            ability: EnemyAbility::Shielded,
+                            chance: 0.25,
+                        },
+                        AbilityChance {
+                            ability: EnemyAbility::Splitting,
+                            chance: 0.25,
+                        },
+                        AbilityChance {
+                            ability: EnemyAbility::Healer,
+                            chance: 0.25,
+                        },
+                        AbilityChance {
+                            ability: EnemyAbility::Sprinter,
+                            chance: 0.25,
+                        },
+                    ],
+                    boss: Some(BossDefinition {
+                        health_multiplier: 20,
+                        phases: vec![
+                            BossPhase {
+                                health_threshold: 0.75,
+                                behaviour: PhaseBehaviour::SpawnMinions(3),
+                            },
+                            BossPhase {
+                                health_threshold: 0.5,
+                                behaviour: PhaseBehaviour::Immune(EnemyColor::Red),
+                            },
+                            BossPhase {
+                                health_threshold: 0.25,
+                                behaviour: PhaseBehaviour::SpeedUp(1.5),
+                            },
+                        ],
+                    }),
+                },
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_wave_repeats() {
+        let waves = Waves::default();
+        let last = waves.waves.len() - 1;
+        assert_eq!(
+            waves.get(last + 3).unwrap().enemies,
+            waves.waves[last].enemies
+        );
+        assert_eq!(waves.definition_index(last + 3), last);
+        assert!(Waves { waves: vec![] }.get(0).is_none());
+    }
+}
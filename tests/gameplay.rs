@@ -0,0 +1,83 @@
+use oicana::bot::{Bot, GreedyStrategy, RandomStrategy};
+use oicana::mods::ModPackage;
+use oicana::simulation::Simulation;
+
+/// Frames per simulated second
+const SECOND: usize = 60;
+
+#[test]
+fn enemy_reaching_the_castle_costs_health() {
+    let mut simulation = Simulation::with_map(Simulation::synthetic_map(3));
+    let health = simulation.game_state().health;
+    simulation.spawn_enemies(1, 100);
+
+    let breached = simulation.step_until(10 * SECOND, |simulation| {
+        simulation.game_state().health < health
+    });
+    assert!(breached);
+}
+
+#[test]
+fn completed_puzzle_builds_tower_on_plot() {
+    let mut simulation = Simulation::with_map(Simulation::synthetic_map(3));
+    let plot = simulation.tower_plots()[0].clone();
+    assert_eq!(simulation.tower_level(&plot), None);
+
+    simulation.fill_puzzle(&plot);
+    simulation.step_frames(2);
+    assert_eq!(simulation.tower_level(&plot), Some(1));
+
+    simulation.fill_puzzle(&plot);
+    simulation.step_frames(2);
+    assert_eq!(simulation.tower_level(&plot), Some(2));
+}
+
+#[test]
+fn tower_kills_enemy_which_becomes_tameable() {
+    let mut simulation = Simulation::with_map(Simulation::synthetic_map(3));
+    simulation.spawn_towers(6);
+    let enemy = simulation.spawn_enemies(1, 20)[0];
+    assert!(!simulation.is_tameable(enemy));
+
+    let tamed = simulation.step_until(3 * SECOND, |simulation| simulation.is_tameable(enemy));
+    assert!(tamed);
+}
+
+#[test]
+fn default_map_runs_without_panicking() {
+    let mut simulation = Simulation::default();
+    simulation.step_frames(5 * SECOND);
+    assert!(simulation.game_state().health > 0);
+}
+
+#[test]
+fn greedy_bot_places_pieces() {
+    let mut simulation = Simulation::default();
+    simulation.add_bot(Bot::new(GreedyStrategy));
+
+    let placed = simulation.step_until(60 * SECOND, |simulation| simulation.filled_slots() > 0);
+    assert!(placed);
+}
+
+#[test]
+fn random_bot_plays_without_panicking() {
+    let mut simulation = Simulation::default();
+    simulation.add_bot(Bot::new(RandomStrategy::new(42)));
+    simulation.step_frames(30 * SECOND);
+}
+
+#[test]
+fn bundled_mod_map_is_playable() {
+    let package = ModPackage::load("long_road", |file| {
+        std::fs::read_to_string(format!("mods/long_road/{}", file)).ok()
+    });
+    assert!(package.is_valid(), "{:?}", package.errors);
+
+    let mut simulation = Simulation::with_map(package.content.map.unwrap());
+    let health = simulation.game_state().health;
+    simulation.spawn_enemies(1, 100);
+    let breached = simulation.step_until(60 * SECOND, |simulation| {
+        simulation.game_state().health < health
+    });
+    assert!(breached);
+}